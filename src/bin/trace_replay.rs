@@ -0,0 +1,35 @@
+//! Replays a trace from the trace recorder through the splitter logic and prints what it did.
+//!
+//! cargo run --features replay --bin trace_replay --target x86_64-unknown-linux-gnu -- path/to/log.txt
+//!
+//! Reads from stdin when no path is given.
+
+use std::io::Read;
+
+fn read_input() -> Result<String, String> {
+    match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|error| format!("could not read {path}: {error}")),
+        None => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|error| format!("could not read stdin: {error}"))?;
+            Ok(text)
+        }
+    }
+}
+
+fn main() {
+    match read_input().and_then(|text| pizza_tower_autosplitter::replay::replay(&text)) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{line}");
+            }
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use asr::string::ArrayCString;
+use bytemuck::{Pod, Zeroable};
+
+use crate::memory::ROOM_NAME_SIZE_CAP;
+
+// the size of the game version string at the start of every layout
+pub const GAME_VERSION_SIZE_CAP: usize = 0x40;
+
+/**
+ * The start of the buffer, the same in every layout, read first to pick the layout of the rest
+ */
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct BufferHeader {
+    pub magic_numbers: [u8; 0x40],
+    pub game_version: ArrayCString<GAME_VERSION_SIZE_CAP>,
+}
+
+/**
+ * The buffer as written by every game version so far:
+ *
+ * 0x00: magic numbers,
+ * 0x40: game version (string),
+ * 0x80: file minutes (f64),
+ * 0x88: file seconds (f64),
+ * 0x90: level minute (f64),
+ * 0x98: level seconds (f64),
+ * 0xA0: current room (string),
+ * 0xE0: end of level fade exists (u8 (bool)),
+ * 0xE1: boss HP (u8)
+ */
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct BufferV1 {
+    pub header: BufferHeader,
+    pub file_minutes: f64,
+    pub file_seconds: f64,
+    pub level_minutes: f64,
+    pub level_seconds: f64,
+    pub room_name: ArrayCString<ROOM_NAME_SIZE_CAP>,
+    pub end_of_level: u8,
+    pub boss_hp: u8,
+    _padding: [u8; 6],
+}
+
+/**
+ * The buffer layouts known to the splitter, a game patch adding fields gets its own
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferLayout {
+    V1,
+}
+
+// game version prefixes and the layout they use, the first match wins
+const LAYOUTS_BY_VERSION: [(&str, BufferLayout); 2] =
+    [("v1.0.", BufferLayout::V1), ("v1.1.", BufferLayout::V1)];
+
+impl BufferLayout {
+    /**
+     * The layout for a game version, None if the version is newer than the splitter
+     */
+    pub fn for_version(game_version: &str) -> Option<Self> {
+        LAYOUTS_BY_VERSION
+            .iter()
+            .find(|(prefix, _)| game_version.starts_with(prefix))
+            .map(|&(_, layout)| layout)
+    }
+
+    /**
+     * Unknown versions are read like the newest layout, new fields are usually added at the end
+     */
+    pub fn latest() -> Self {
+        BufferLayout::V1
+    }
+}
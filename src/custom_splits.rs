@@ -0,0 +1,136 @@
+use asr::arrayvec::{ArrayString, ArrayVec};
+use asr::{itoa, print_message};
+
+use crate::memory::ROOM_NAME_SIZE_CAP;
+
+// custom_split_1 to custom_split_16 are read from the settings map
+pub const CUSTOM_SPLIT_CAPACITY: usize = 16;
+const CUSTOM_SPLIT_KEY: &str = "custom_split_";
+// room patterns on both sides plus the arrow
+const CUSTOM_SPLIT_TEXT_CAP: usize = ROOM_NAME_SIZE_CAP * 2 + 8;
+
+/**
+ * A room transition the runner wants a split on, written as "from_room -> to_room".
+ * A pattern ending in * matches every room starting with the rest, a lone * matches any room
+ */
+#[derive(Clone, Copy)]
+pub struct CustomSplit {
+    from: ArrayString<ROOM_NAME_SIZE_CAP>,
+    to: ArrayString<ROOM_NAME_SIZE_CAP>,
+}
+
+fn room_matches(pattern: &str, room: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => room.starts_with(prefix),
+        None => pattern == room,
+    }
+}
+
+impl CustomSplit {
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let (from, to) = text
+            .split_once("->")
+            .ok_or("Custom split is missing the \"->\" between the rooms")?;
+        let (from, to) = (from.trim(), to.trim());
+        if from.is_empty() || to.is_empty() {
+            return Err("Custom split needs a room on both sides of the \"->\"");
+        }
+        Ok(Self {
+            from: ArrayString::from(from).map_err(|_| "Custom split room name is too long")?,
+            to: ArrayString::from(to).map_err(|_| "Custom split room name is too long")?,
+        })
+    }
+
+    pub fn matches(&self, old_room: &str, new_room: &str) -> bool {
+        room_matches(&self.from, old_room) && room_matches(&self.to, new_room)
+    }
+
+    pub fn old_room_pattern(&self) -> &str {
+        &self.from
+    }
+
+    pub fn new_room_pattern(&self) -> &str {
+        &self.to
+    }
+}
+
+fn custom_split_key(number: usize) -> ArrayString<32> {
+    let mut key = ArrayString::new();
+    key.push_str(CUSTOM_SPLIT_KEY);
+    key.push_str(itoa::Buffer::new().format(number));
+    key
+}
+
+/**
+ * The custom splits set in the layout settings, checked on every room change
+ */
+#[derive(Default, Clone)]
+pub struct CustomSplits {
+    splits: ArrayVec<CustomSplit, CUSTOM_SPLIT_CAPACITY>,
+    // the settings map entries the splits were parsed from, empty for missing ones
+    texts: [ArrayString<CUSTOM_SPLIT_TEXT_CAP>; CUSTOM_SPLIT_CAPACITY],
+}
+
+impl CustomSplits {
+    /**
+     * Read custom_split_1 to custom_split_16 from the settings map, skipping missing ones.
+     * There is no text box in the settings GUI, so these are set in the layout or splits file
+     */
+    pub fn load() -> Self {
+        let mut custom_splits = Self::default();
+        custom_splits.reload();
+        custom_splits
+    }
+
+    /**
+     * Read the entries again and parse them if any of them changed since the last time,
+     * returns true if the splits changed
+     */
+    pub fn reload(&mut self) -> bool {
+        let settings_map = asr::settings::Map::load();
+        let mut texts = [ArrayString::new(); CUSTOM_SPLIT_CAPACITY];
+
+        for (number, text) in (1..=CUSTOM_SPLIT_CAPACITY).zip(texts.iter_mut()) {
+            if let Some(value) = settings_map
+                .get(&custom_split_key(number))
+                .and_then(|value| value.get_array_string::<CUSTOM_SPLIT_TEXT_CAP>())
+                .and_then(|value| value.ok())
+            {
+                *text = value;
+            }
+        }
+        if texts == self.texts {
+            return false;
+        }
+
+        self.splits.clear();
+        for (number, text) in (1..=CUSTOM_SPLIT_CAPACITY).zip(texts.iter()) {
+            if text.is_empty() {
+                continue;
+            }
+            match CustomSplit::parse(text) {
+                Ok(split) => self.push(split),
+                Err(error) => {
+                    print_message(&custom_split_key(number));
+                    print_message(error);
+                }
+            }
+        }
+        self.texts = texts;
+        true
+    }
+
+    pub fn push(&mut self, split: CustomSplit) {
+        let _ = self.splits.try_push(split);
+    }
+
+    pub fn matches(&self, old_room: &str, new_room: &str) -> bool {
+        self.splits
+            .iter()
+            .any(|split| split.matches(old_room, new_room))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CustomSplit> {
+        self.splits.iter()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::memory::{GameSignature, MemorySource};
+use asr::Address;
+use bytemuck::CheckedBitPattern;
+
+/**
+ * A block of fake game memory starting at a given address
+ */
+pub struct FakeRange<'a> {
+    pub address: Address,
+    pub bytes: &'a [u8],
+}
+
+/**
+ * An in-memory stand-in for the game process, reads and signature scans are served from the given ranges
+ */
+pub struct FakeProcess<'a> {
+    pub ranges: &'a [FakeRange<'a>],
+}
+
+impl FakeProcess<'_> {
+    /**
+     * the bytes from the address until the end of the range that contains it
+     */
+    fn bytes_at(&self, address: Address) -> Option<&[u8]> {
+        self.ranges.iter().find_map(|range| {
+            let start = range.address.value();
+            let end = start + range.bytes.len() as u64;
+            if (start..end).contains(&address.value()) {
+                Some(&range.bytes[(address.value() - start) as usize..])
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/**
+ * true if the bytes start with the pattern, "??" matches any byte
+ */
+fn pattern_matches(pattern: &str, bytes: &[u8]) -> bool {
+    let mut bytes = bytes.iter();
+    for token in pattern.split_whitespace() {
+        let Some(&byte) = bytes.next() else {
+            return false;
+        };
+        if token == "??" {
+            continue;
+        }
+        if u8::from_str_radix(token, 16) != Ok(byte) {
+            return false;
+        }
+    }
+    true
+}
+
+impl MemorySource for FakeProcess<'_> {
+    fn read<T: CheckedBitPattern>(&self, address: impl Into<Address>) -> Result<T, ()> {
+        let bytes = self.bytes_at(address.into()).ok_or(())?;
+        let size = core::mem::size_of::<T>();
+        if bytes.len() < size {
+            return Err(());
+        }
+        bytemuck::checked::try_pod_read_unaligned(&bytes[..size]).map_err(|_| ())
+    }
+
+    fn memory_ranges(&self) -> impl DoubleEndedIterator<Item = (Address, u64)> + '_ {
+        self.ranges
+            .iter()
+            .map(|range| (range.address, range.bytes.len() as u64))
+    }
+
+    fn scan_range<const N: usize>(
+        &self,
+        signature: &GameSignature<N>,
+        range: (Address, u64),
+    ) -> Option<Address> {
+        let bytes = self.bytes_at(range.0)?;
+        let bytes = &bytes[..bytes.len().min(range.1 as usize)];
+        (0..bytes.len())
+            .find(|&offset| pattern_matches(signature.pattern(), &bytes[offset..]))
+            .map(|offset| Address::new(range.0.value() + offset as u64))
+    }
+}
@@ -1,19 +1,42 @@
 #![no_std]
 
+// host builds (tests and tools) get std, the wasm splitter stays no_std
+#[cfg(not(target_family = "wasm"))]
+extern crate std;
+
 use asr::string::ArrayCString;
 use asr::time::Duration;
-use asr::timer::TimerState;
 use asr::{future::next_tick, settings::Gui, watcher::Pair, Process};
 use asr::{print_message, timer};
-use memory::{refresh_mem_values, ROOM_NAME_SIZE_CAP};
-use room_names::Level;
-use settings::TimerMode;
-
+use buffer_helper::GAME_VERSION_SIZE_CAP;
+use custom_splits::CustomSplits;
+use memory::{
+    display_mem_values, display_tick_cost, refresh_mem_values, CountingSource, ROOM_NAME_SIZE_CAP,
+};
+use route::Route;
+use splitter::{SplitterState, TimerAction};
+use trace::TraceRecorder;
+
+mod buffer_helper;
+mod custom_splits;
+#[cfg(test)]
+mod fake_process;
 mod memory;
+mod practice;
+#[cfg(any(test, feature = "replay"))]
+pub mod replay;
+mod room_catalogue;
 mod room_names;
+mod route;
 mod settings;
+mod splitter;
+#[cfg(test)]
+mod tests;
+mod trace;
 
+#[cfg(target_family = "wasm")]
 asr::async_main!(stable);
+#[cfg(target_family = "wasm")]
 asr::panic_handler!();
 
 const MAIN_MODULE: &str = "PizzaTower.exe";
@@ -30,7 +53,7 @@ struct MemoryAddresses {
 
 #[derive(Default)]
 struct MemoryValues {
-    game_version: Pair<ArrayCString<0x40>>,
+    game_version: Pair<ArrayCString<GAME_VERSION_SIZE_CAP>>,
     room_id: Pair<i32>,
     room_name: Pair<ArrayCString<ROOM_NAME_SIZE_CAP>>,
     file_seconds: Pair<f64>,
@@ -41,6 +64,26 @@ struct MemoryValues {
     boss_hp: Pair<u8>,
 }
 
+/**
+ * send an action decided by the splitter to livesplit
+ */
+fn do_timer_action(action: TimerAction) {
+    match action {
+        TimerAction::Start => timer::start(),
+        TimerAction::Split => timer::split(),
+        TimerAction::SkipSplit => timer::skip_split(),
+        TimerAction::Reset => timer::reset(),
+        TimerAction::SetGameTime(seconds) => {
+            // makes the livesplit game time frozen, if not used it stutters when the igt stops advancing
+            timer::pause_game_time();
+            timer::set_game_time(Duration::seconds_f64(seconds));
+        }
+        TimerAction::PauseGameTime => timer::pause_game_time(),
+        TimerAction::ResumeGameTime => timer::resume_game_time(),
+    }
+}
+
+#[cfg_attr(not(target_family = "wasm"), allow(dead_code))]
 async fn main() {
     let mut settings = settings::Settings::register();
     if settings.timer_mode_load_defaults {
@@ -48,6 +91,12 @@ async fn main() {
     }
     let mut mem_addresses = MemoryAddresses::default();
     let mut mem_values = MemoryValues::default();
+    let mut trace = TraceRecorder::new();
+    let mut unknown_rooms = room_catalogue::UnknownRooms::new();
+    let mut custom_splits = CustomSplits::load();
+    let mut route = Route::load();
+    let mut practice = practice::Practice::new();
+    let mut best_times = practice::SettingsMapBestTimes::new();
 
     asr::set_tick_rate(TICK_RATE_MAIN_LOOP);
 
@@ -86,7 +135,7 @@ async fn main() {
                 }
 
                 if mem_addresses.room_id.is_some() {
-                    if let Ok(room_id_result) = process.read(mem_addresses.main_address.unwrap_or(asr::Address::default()).value() + mem_addresses.room_id.unwrap().value()) {
+                    if let Ok(room_id_result) = memory::read_room_id(&process, &mem_addresses) {
                         mem_values.room_id.current = room_id_result
                     } else {
                         mem_values.room_id.current = 0;
@@ -96,13 +145,11 @@ async fn main() {
                         print_message("Waiting for the game to start...");
                     }
                     while mem_values.room_id.current == 0 {
-                        if mem_values.room_id.current == 0 {
-                            if let Ok(value) = process.read::<i32>(mem_addresses.main_address.unwrap_or(asr::Address::default()).value() + mem_addresses.room_id.unwrap().value()) {
-                                mem_values.room_id.current = value;
-                            } else {
-                                asr::set_tick_rate(TICK_RATE_MAIN_LOOP);
-                                break;
-                            }
+                        if let Ok(value) = memory::read_room_id(&process, &mem_addresses) {
+                            mem_values.room_id.current = value;
+                        } else {
+                            asr::set_tick_rate(TICK_RATE_MAIN_LOOP);
+                            break;
                         }
                     }
                 }
@@ -118,158 +165,70 @@ async fn main() {
 
                     asr::set_tick_rate(TICK_RATE_MAIN_LOOP);
 
-                    // variables declaration for the main loop
-                    let mut current_level = room_names::Level::Unknown;
-                    let mut igt_file_secs_calculated: Pair<f64> = Pair::default();
-                    let mut igt_level_secs_calculated: Pair<f64> = Pair::default();
-
-                    let mut ng_plus_offset_seconds: Option<f64> = None;
-                    let mut iw_offset_seconds: Option<f64> = None;
-
-                    let mut enable_full_game_split = false;
-                    let mut ctop_oob_split = false; // should only happen once per run
-
-                    let mut last_room_split_name = ArrayCString::<ROOM_NAME_SIZE_CAP>::new();
-                    let mut last_room_split_time = 0.0;
+                    let mut splitter = SplitterState::new(mem_addresses.buffer_helper.is_some());
+                    custom_splits.reload();
+                    splitter.set_custom_splits(custom_splits.clone());
+                    route.reload();
+                    splitter.set_route(route.clone());
 
                     loop {
                         settings.update();
                         if settings.timer_mode.changed() {
                             settings.load_default_settings_for_mode();
                         }
+                        if custom_splits.reload() {
+                            splitter.set_custom_splits(custom_splits.clone());
+                        }
+                        if route.reload() {
+                            splitter.set_route(route.clone());
+                        }
 
-                        if let Err(text) = refresh_mem_values(&process, &mem_addresses, &mut mem_values) {
+                        let counted_process = CountingSource::new(&process);
+                        if let Err(text) = refresh_mem_values(&counted_process, &mem_addresses, &mut mem_values) {
                             print_message(text);
                             print_message("Exiting main loop and retrying...");
                             break;
                         }
-
-                        let room_name_parsed_current = mem_values.room_name.current.validate_utf8().unwrap_or("(invalid utf8 string)");
-                        let room_name_parsed_old = mem_values.room_name.old.validate_utf8().unwrap_or("(invalid utf8 string)");
-
-                        // update current level and enable full game splits
+                        display_mem_values(&mem_addresses, &mem_values);
                         if mem_values.room_name.changed() {
-                            current_level = room_names::get_current_level(room_name_parsed_current, current_level);
-                            if !enable_full_game_split {
-                                enable_full_game_split = room_names::full_game_split_unlock_rooms(room_name_parsed_current);
-                            }
-
+                            unknown_rooms.check(&mem_values.room_name.current);
                         }
-
-                        timer::set_variable("Current Level", room_names::get_full_level_name(&current_level));
-
-                        // game time set
-                        if mem_addresses.buffer_helper.is_some() {
-
-                            igt_file_secs_calculated.old = igt_file_secs_calculated.current;
-                            igt_file_secs_calculated.current = mem_values.file_minutes.current * 60.0 + mem_values.file_seconds.current;
-                            igt_level_secs_calculated.old =  igt_level_secs_calculated.current;
-                            igt_level_secs_calculated.current = mem_values.level_minutes.current * 60.0 + mem_values.level_seconds.current;
-
-                            // offsets for ng+ and iw
-                            if timer::state() == TimerState::NotRunning {
-                                // ng+ offset update
-                                if ng_plus_offset_seconds.is_none() && room_name_parsed_current == "tower_entrancehall" && mem_values.level_minutes.current == 0.0 && mem_values.level_seconds.current < 1.0 {
-                                    ng_plus_offset_seconds = Some(igt_file_secs_calculated.current);
-                                }
-                                if ng_plus_offset_seconds.is_some() && (room_name_parsed_current == "hub_loadingscreen" || room_name_parsed_current == "Finalintro") {
-                                    ng_plus_offset_seconds = None;
-                                }
-
-                                // iw offset update
-                                if iw_offset_seconds.is_none() && current_level == room_names::Level::Hub {
-                                    iw_offset_seconds = Some(igt_file_secs_calculated.current);
-                                }
-                                if iw_offset_seconds.is_some() && current_level != room_names::Level::Hub {
-                                    iw_offset_seconds = None;
-                                }
-                            }
-
-                            // makes the livesplit game time frozen, if not used it stutters when the igt stops advancing
-                            timer::pause_game_time();
-
-
-                            let game_time_livesplit = match settings.timer_mode.current {
-                                TimerMode::FullGame => igt_file_secs_calculated.current,
-                                TimerMode::IL => igt_level_secs_calculated.current,
-                                TimerMode::NewGamePlus => igt_file_secs_calculated.current - ng_plus_offset_seconds.unwrap_or(0.0),
-                                TimerMode::IW => igt_file_secs_calculated.current - iw_offset_seconds.unwrap_or(0.0),
-                            };
-                            timer::set_game_time(Duration::seconds_f64(game_time_livesplit));
+                        display_tick_cost(counted_process.reads());
+
+                        let timer_state = timer::state();
+                        splitter.sync_split_index(timer::current_split_index(), settings.splits_route);
+                        let actions = splitter.tick(&mem_values, &settings, timer_state);
+
+                        timer::set_variable("Current Level", room_names::get_full_level_name(&splitter.current_level(), splitter.campaign()));
+                        timer::set_variable("Campaign", splitter.campaign().name());
+                        timer::set_variable("Current IL", match splitter.current_il() {
+                            Some(level) => room_names::get_full_level_name(&level, splitter.campaign()),
+                            None => "None",
+                        });
+                        memory::set_variable_int("Current Lap", splitter.current_lap());
+                        memory::display_secrets(splitter.secret_counts());
+
+                        if settings.practice_reset {
+                            best_times.clear();
+                            practice = practice::Practice::new();
+                            settings.uncheck_practice_reset();
                         }
-
-                        // reset
-                        if settings.reset_enable {
-                            if settings.reset_new_file && room_name_parsed_current == "Finalintro" && room_name_parsed_old != "Finalintro" {
-                                timer::reset();
-                            }
-                            if settings.reset_any_file && mem_values.room_name.changed() && room_name_parsed_current == "hub_loadingscreen" {
-                                timer::reset();
-                            }
-                            if settings.reset_new_level && igt_level_secs_calculated.decreased() && current_level != Level::Hub {
-                                last_room_split_time = 0.0;
-                                timer::reset();
-                            }
+                        if settings.practice_enable {
+                            practice.tick(&mem_values, splitter.current_level(), &mut best_times);
+                            best_times.store();
+                            memory::display_practice(&practice);
                         }
 
-                        // split
-                        if settings.splits_enable {
-
-                            // covers any full game split
-                            if settings.splits_level_end {
-
-                                // standard level / boss end
-                                // got lazy and hardcoded the noise pizzaface split here :)
-                                if mem_values.room_name.changed()
-                                && room_names::full_game_split_rooms(room_name_parsed_old)
-                                && (current_level == Level::Hub || current_level == Level::ResultsScreen)
-                                && enable_full_game_split
-                                && (mem_values.boss_hp.old == 0 || (room_name_parsed_current == "boss_pizzafacehub" && room_name_parsed_old == "boss_pizzaface")) {
-                                    timer::split();
-                                    enable_full_game_split = false;
-                                }
-
-                                // end of the run frame perfect split, technically the prev "if" could cover this too but frame perfectly splitting at the end is cooler
-                                if mem_values.end_of_level.current == 1 && mem_values.end_of_level.old == 0 && room_name_parsed_current == "tower_entrancehall" {
-                                    timer::split();
-                                }
-
-                                // ctop entering from oob
-                                if timer::state() == TimerState::NotRunning && ctop_oob_split {
-                                    ctop_oob_split = false;
-                                }
-                                if room_name_parsed_current == "tower_finalhallway" && room_name_parsed_old == "tower_5" && !ctop_oob_split {
-                                    ctop_oob_split = true;
-                                    timer::split();
-                                }
-                            }
-
-                            let last_room_transition_time = igt_file_secs_calculated.current - last_room_split_time;
-                            if settings.splits_rooms
-                            && (last_room_transition_time > 2.0 || last_room_transition_time < 0.0 || mem_values.room_name.current != last_room_split_name)
-                            && mem_values.room_name.changed() && room_name_parsed_old != "rank_room" {
-                                last_room_split_time = igt_file_secs_calculated.current;
-                                last_room_split_name = mem_values.room_name.old;
-
-                                asr::timer::split();
-                            }
-
+                        if settings.trace_enable {
+                            trace.record(&mem_values, timer_state, &actions);
+                        }
+                        if settings.trace_dump {
+                            trace.dump(&settings, splitter.custom_splits(), splitter.route(), splitter.game_time_available());
+                            settings.uncheck_trace_dump();
                         }
 
-                        // start
-                        if settings.start_enable {
-                            if settings.start_new_file && room_name_parsed_current == "tower_entrancehall" && room_name_parsed_old == "Finalintro" {
-                                timer::start();
-                            }
-                            if settings.start_any_file && room_name_parsed_current == "tower_entrancehall" && room_name_parsed_old == "hub_loadingscreen" {
-                                timer::start();
-                            }
-                            if settings.start_new_il && room_names::get_starting_room(&current_level) == room_name_parsed_current && igt_level_secs_calculated.current > 0.07 && igt_level_secs_calculated.current <= 0.1 {
-                                timer::start();
-                            }
-                            if settings.start_exit_level && mem_values.room_name.changed() && room_names::full_game_split_rooms(room_name_parsed_old) && current_level == Level::Hub {
-                                timer::start();
-                            }
+                        for action in actions {
+                            do_timer_action(action);
                         }
 
                         next_tick().await;
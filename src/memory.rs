@@ -1,58 +1,175 @@
+use crate::buffer_helper::{BufferHeader, BufferLayout, BufferV1};
+use crate::practice::Practice;
+use crate::room_names;
 use crate::{MemoryAddresses, MemoryValues};
+use asr::arrayvec::ArrayString;
 use asr::{itoa, ryu, signature::Signature, string::ArrayCString, watcher::Pair, Address, Process};
+use bytemuck::CheckedBitPattern;
+use core::cell::Cell;
+use core::mem::offset_of;
 use itoa::Integer;
 
 // the array with all the room names
-const ROOM_ID_ARRAY_SIG: Signature<13> = Signature::new("74 0C 48 8B 05 ?? ?? ?? ?? 48 8B 04 D0");
+const ROOM_ID_ARRAY_SIG: GameSignature<13> =
+    GameSignature::new("74 0C 48 8B 05 ?? ?? ?? ?? 48 8B 04 D0");
 // the id of the current room the player is on (i32)
-const ROOM_ID_SIG: Signature<9> = Signature::new("89 3D ?? ?? ?? ?? 48 3B 1D");
+const ROOM_ID_SIG: GameSignature<9> = GameSignature::new("89 3D ?? ?? ?? ?? 48 3B 1D");
 
 // the magic numbers to find for the buffer, using 16 of the 32, good enough
-const BUFFER_MAGIC_NUMBER: Signature<16> =
-    Signature::new("C2 5A 17 65 BE 4D DF D6 F2 1C D1 3B A7 A6 1F C3");
+const BUFFER_MAGIC_NUMBER: GameSignature<16> =
+    GameSignature::new("C2 5A 17 65 BE 4D DF D6 F2 1C D1 3B A7 A6 1F C3");
 
 pub const ROOM_NAME_SIZE_CAP: usize = 0x40;
 
 /**
- * update an f64 pair and display it in the variable view of livesplit
+ * A signature together with the pattern it was built from, so sources that can't use asr's scanner can still match it
  */
-fn update_pair_f64(variable_name: &str, new_value: f64, pair: &mut Pair<f64>) {
-    let mut buffer = ryu::Buffer::new();
-    asr::timer::set_variable(variable_name, buffer.format(new_value));
+#[derive(Clone, Copy)]
+pub struct GameSignature<const N: usize> {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pattern: &'static str,
+    signature: Signature<N>,
+}
+
+impl<const N: usize> GameSignature<N> {
+    pub const fn new(pattern: &'static str) -> Self {
+        Self {
+            pattern,
+            signature: Signature::new(pattern),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn pattern(&self) -> &'static str {
+        self.pattern
+    }
+}
+
+/**
+ * Anywhere the game memory can be read from, the real game process or a fake one for testing
+ */
+pub trait MemorySource {
+    fn read<T: CheckedBitPattern>(&self, address: impl Into<Address>) -> Result<T, ()>;
+
+    /**
+     * every memory range of the process as (start address, size)
+     */
+    fn memory_ranges(&self) -> impl DoubleEndedIterator<Item = (Address, u64)> + '_;
+
+    fn scan_range<const N: usize>(
+        &self,
+        signature: &GameSignature<N>,
+        range: (Address, u64),
+    ) -> Option<Address>;
+}
+
+impl MemorySource for Process {
+    fn read<T: CheckedBitPattern>(&self, address: impl Into<Address>) -> Result<T, ()> {
+        Process::read(self, address).map_err(|_| ())
+    }
+
+    fn memory_ranges(&self) -> impl DoubleEndedIterator<Item = (Address, u64)> + '_ {
+        Process::memory_ranges(self).map(|range| {
+            (
+                range.address().unwrap_or_default(),
+                range.size().unwrap_or_default(),
+            )
+        })
+    }
+
+    fn scan_range<const N: usize>(
+        &self,
+        signature: &GameSignature<N>,
+        range: (Address, u64),
+    ) -> Option<Address> {
+        signature.signature.scan_process_range(self, range)
+    }
+}
+
+/**
+ * Counts the reads going through it, every read is a call into the other process so this is what a tick costs
+ */
+pub struct CountingSource<'a, S: MemorySource> {
+    source: &'a S,
+    reads: Cell<u32>,
+}
+
+impl<'a, S: MemorySource> CountingSource<'a, S> {
+    pub fn new(source: &'a S) -> Self {
+        Self {
+            source,
+            reads: Cell::new(0),
+        }
+    }
+
+    pub fn reads(&self) -> u32 {
+        self.reads.get()
+    }
+}
+
+impl<S: MemorySource> MemorySource for CountingSource<'_, S> {
+    fn read<T: CheckedBitPattern>(&self, address: impl Into<Address>) -> Result<T, ()> {
+        self.reads.set(self.reads.get() + 1);
+        self.source.read(address)
+    }
+
+    fn memory_ranges(&self) -> impl DoubleEndedIterator<Item = (Address, u64)> + '_ {
+        self.source.memory_ranges()
+    }
+
+    fn scan_range<const N: usize>(
+        &self,
+        signature: &GameSignature<N>,
+        range: (Address, u64),
+    ) -> Option<Address> {
+        self.source.scan_range(signature, range)
+    }
+}
+
+/**
+ * move the current value of a pair to old and store the new one
+ */
+fn update_pair<T: Copy>(new_value: T, pair: &mut Pair<T>) {
     pair.old = pair.current;
     pair.current = new_value;
 }
 
+fn game_version_str(memory_values: &MemoryValues) -> &str {
+    memory_values
+        .game_version
+        .current
+        .validate_utf8()
+        .unwrap_or_default()
+}
+
 /**
- * update an integer pair and display it in the variable view of livesplit
+ * display an f64 in the variable view of livesplit
  */
-fn update_pair_int<T: Integer>(variable_name: &str, new_value: T, pair: &mut Pair<T>) {
+fn set_variable_f64(variable_name: &str, value: f64) {
+    let mut buffer = ryu::Buffer::new();
+    asr::timer::set_variable(variable_name, buffer.format(value));
+}
+
+/**
+ * display an integer in the variable view of livesplit
+ */
+pub fn set_variable_int<T: Integer>(variable_name: &str, value: T) {
     let mut buffer = itoa::Buffer::new();
-    asr::timer::set_variable(variable_name, buffer.format(new_value));
-    pair.old = pair.current;
-    pair.current = new_value;
+    asr::timer::set_variable(variable_name, buffer.format(value));
 }
 
 /**
- * update a cstring pair and display it in the variable view of livesplit
+ * display a cstring in the variable view of livesplit
  */
-fn update_pair_cstring(
-    variable_name: &str,
-    new_value: ArrayCString<ROOM_NAME_SIZE_CAP>,
-    pair: &mut Pair<ArrayCString<ROOM_NAME_SIZE_CAP>>,
-) {
+fn set_variable_cstring(variable_name: &str, value: &ArrayCString<ROOM_NAME_SIZE_CAP>) {
     asr::timer::set_variable(
         variable_name,
-        new_value
-            .validate_utf8()
-            .unwrap_or("(invalid utf-8 string)"),
+        value.validate_utf8().unwrap_or("(invalid utf-8 string)"),
     );
-    pair.old = pair.current;
-    pair.current = new_value;
 }
 
 pub fn room_id_sigscan_start(
-    process: &asr::Process,
+    process: &impl MemorySource,
     addresses: &MemoryAddresses,
 ) -> Result<asr::Address, ()> {
     let main_address = addresses.main_address.unwrap_or(Address::new(0));
@@ -61,10 +178,7 @@ pub fn room_id_sigscan_start(
     asr::print_message("Starting the room id signature scan...");
     let mut room_id_address: Option<Address> = None;
     for range in process.memory_ranges().rev() {
-        let address = range.address().unwrap_or_default().value();
-        let size = range.size().unwrap_or_default();
-
-        if let Some(add) = ROOM_ID_SIG.scan_process_range(process, (address, size)) {
+        if let Some(add) = process.scan_range(&ROOM_ID_SIG, range) {
             let offset = match process.read::<u32>(Address::new(add.value() + 0x2)) {
                 Ok(offset) => offset,
                 Err(_) => {
@@ -96,15 +210,13 @@ pub fn room_id_sigscan_start(
     }
 }
 
-pub fn buffer_helper_sigscan_init(process: &asr::Process) -> Result<asr::Address, ()> {
+pub fn buffer_helper_sigscan_init(process: &impl MemorySource) -> Result<asr::Address, ()> {
     asr::print_message("Starting the helper buffer signature scan...");
 
     let mut helper_address: Option<Address> = None;
 
     for range in process.memory_ranges() {
-        let address = range.address().unwrap_or_default().value();
-        let size = range.size().unwrap_or_default();
-        if let Some(address) = BUFFER_MAGIC_NUMBER.scan_process_range(process, (address, size)) {
+        if let Some(address) = process.scan_range(&BUFFER_MAGIC_NUMBER, range) {
             helper_address = Some(address);
             break;
         }
@@ -121,20 +233,21 @@ pub fn buffer_helper_sigscan_init(process: &asr::Process) -> Result<asr::Address
         Ok(add)
     } else {
         asr::print_message("Could not complete the buffer helper sigscan. Is the \"-livesplit\" launch option set?");
-        asr::print_message("Continuing with the basic real time and split features.");
+        asr::print_message(
+            "Continuing with real time and the split features, game time only removes loading screens.",
+        );
         Err(())
     }
 }
 
-pub fn room_name_array_sigscan_start(process: &asr::Process) -> Result<asr::Address, &str> {
+pub fn room_name_array_sigscan_start(
+    process: &impl MemorySource,
+) -> Result<asr::Address, &'static str> {
     asr::print_message("Starting the name array signature scan...");
     let mut pointer_to_rooms_array: Option<Address> = None;
     // get pointer scan add -> read u32 5 bytes after the result to find offset -> result is add scanned + 9 + offset
     for range in process.memory_ranges().rev() {
-        let address = range.address().unwrap_or_default().value();
-        let size = range.size().unwrap_or_default();
-
-        if let Some(add) = ROOM_ID_ARRAY_SIG.scan_process_range(process, (address, size)) {
+        if let Some(add) = process.scan_range(&ROOM_ID_ARRAY_SIG, range) {
             let offset = match process.read::<u32>(Address::new(add.value() + 0x5)) {
                 Ok(pointer) => pointer,
                 Err(_) => return Err("Could not read offset to find the room names array"),
@@ -158,90 +271,69 @@ pub fn room_name_array_sigscan_start(process: &asr::Process) -> Result<asr::Addr
     }
 }
 
-pub fn refresh_mem_values<'a>(
-    process: &'a Process,
-    memory_addresses: &'a MemoryAddresses,
+/**
+ * read the id of the room the player is currently in
+ */
+pub fn read_room_id(
+    process: &impl MemorySource,
+    memory_addresses: &MemoryAddresses,
+) -> Result<i32, ()> {
+    let main_address = memory_addresses.main_address.unwrap_or_default();
+    let room_id = memory_addresses.room_id.unwrap_or_default();
+
+    process.read::<i32>(Address::new(main_address.value() + room_id.value()))
+}
+
+pub fn refresh_mem_values(
+    process: &impl MemorySource,
+    memory_addresses: &MemoryAddresses,
     memory_values: &mut MemoryValues,
-) -> Result<(), &'a str> {
-    let main_address;
-    if let Some(address) = memory_addresses.main_address {
-        main_address = address;
-    } else {
+) -> Result<(), &'static str> {
+    if memory_addresses.main_address.is_none() {
         return Err("Main Address is None in refresh mem values function");
     }
 
-    if let Ok(value) = process.read::<i32>(Address::new(
-        memory_addresses.room_id.unwrap_or(Address::new(0)).value() + main_address.value(),
-    )) {
-        update_pair_int("Room ID", value, &mut memory_values.room_id);
+    if let Ok(value) = read_room_id(process, memory_addresses) {
+        update_pair(value, &mut memory_values.room_id);
     } else {
         return Err("Could not read the room ID from memory");
     }
 
     // only update if buffer helper was found
-    if memory_addresses.buffer_helper.is_some() {
-        /*
-        Buffer documentation:
-        0x00: magic numbers
-        0x40: game version (string)
-        0x80: file minutes (f64)
-        0x88: file seconds (f64)
-        0x90: level minute (f64)
-        0x98: level seconds (f64)
-        0xA0: current room (string)
-        0xE0: end of level fade exists (u8 (bool))
-        0xE1: boss HP (u8)
-        */
-
-        let buffer_helper_add = memory_addresses
-            .buffer_helper
-            .unwrap_or(Address::new(0))
-            .value();
-
+    if let Some(buffer_helper) = memory_addresses.buffer_helper {
         // game version doesn't need to be updated more than once...
         if memory_values.game_version.current == ArrayCString::default() {
-            let game_version = buffer_helper_add + 0x40;
-
-            if let Ok(value) = process.read::<ArrayCString<ROOM_NAME_SIZE_CAP>>(game_version) {
-                update_pair_cstring("Game Version", value, &mut memory_values.game_version);
+            if let Ok(header) = process.read::<BufferHeader>(buffer_helper) {
+                update_pair(header.game_version, &mut memory_values.game_version);
+                // the game writes its version a little after the buffer appears
+                let game_version = game_version_str(memory_values);
+                if !game_version.is_empty() && BufferLayout::for_version(game_version).is_none() {
+                    asr::print_message(
+                        "Unknown game version, reading the buffer helper like the newest known one",
+                    );
+                }
             }
         }
 
-        let file_minutes_add = buffer_helper_add + 0x80;
-        let file_seconds_add = buffer_helper_add + 0x88;
-        let level_minutes_add = buffer_helper_add + 0x90;
-        let level_seconds_add = buffer_helper_add + 0x98;
-        let room_add = buffer_helper_add + 0xA0;
-        let end_level_fade_add = buffer_helper_add + 0xE0;
-        let boss_hp_add = buffer_helper_add + 0xE1;
-
-        if let Ok(value) = process.read::<f64>(Address::new(file_seconds_add)) {
-            update_pair_f64("File Seconds", value, &mut memory_values.file_seconds);
-        };
-
-        if let Ok(value) = process.read::<f64>(Address::new(file_minutes_add)) {
-            update_pair_f64("File Minutes", value, &mut memory_values.file_minutes);
-        };
-
-        if let Ok(value) = process.read::<f64>(Address::new(level_seconds_add)) {
-            update_pair_f64("Level Seconds", value, &mut memory_values.level_seconds);
-        };
-
-        if let Ok(value) = process.read::<f64>(Address::new(level_minutes_add)) {
-            update_pair_f64("Level Minutes", value, &mut memory_values.level_minutes);
-        };
-
-        if let Ok(value) = process.read::<ArrayCString<ROOM_NAME_SIZE_CAP>>(room_add) {
-            update_pair_cstring("Room Name (Buffer)", value, &mut memory_values.room_name);
+        let layout = BufferLayout::for_version(game_version_str(memory_values))
+            .unwrap_or(BufferLayout::latest());
+
+        match layout {
+            BufferLayout::V1 => {
+                if let Ok(buffer) = process.read::<BufferV1>(buffer_helper) {
+                    update_pair(buffer.file_seconds, &mut memory_values.file_seconds);
+                    update_pair(buffer.file_minutes, &mut memory_values.file_minutes);
+                    update_pair(buffer.level_seconds, &mut memory_values.level_seconds);
+                    update_pair(buffer.level_minutes, &mut memory_values.level_minutes);
+                    update_pair(buffer.room_name, &mut memory_values.room_name);
+                    update_pair(buffer.end_of_level, &mut memory_values.end_of_level);
+                    update_pair(buffer.boss_hp, &mut memory_values.boss_hp);
+                } else {
+                    // the whole block can fail when part of it isn't readable, get what we can
+                    refresh_buffer_v1_fields(process, buffer_helper, memory_values);
+                }
+            }
         }
-
-        if let Ok(value) = process.read::<u8>(Address::new(end_level_fade_add)) {
-            update_pair_int("End Fade Exists", value, &mut memory_values.end_of_level);
-        };
-
-        if let Ok(value) = process.read::<u8>(Address::new(boss_hp_add)) {
-            update_pair_int("Boss HP", value, &mut memory_values.boss_hp);
-        };
     } else {
         // with the current room id value as an offset, find its name in the array
         let curr_room_name_add = process.read::<u64>(Address::new(
@@ -255,11 +347,7 @@ pub fn refresh_mem_values<'a>(
         match curr_room_name_add {
             Ok(add) => {
                 if let Ok(value) = process.read::<ArrayCString<ROOM_NAME_SIZE_CAP>>(add) {
-                    update_pair_cstring(
-                        "Room Name (GM Array)",
-                        value,
-                        &mut memory_values.room_name,
-                    );
+                    update_pair(value, &mut memory_values.room_name);
                 }
             }
             Err(_) => return Err("Could not read the room address, retrying signature scan..."),
@@ -268,3 +356,149 @@ pub fn refresh_mem_values<'a>(
 
     Ok(())
 }
+
+/**
+ * read the buffer helper one field at a time, the fields that can't be read keep their old value
+ */
+fn refresh_buffer_v1_fields(
+    process: &impl MemorySource,
+    buffer_helper: Address,
+    memory_values: &mut MemoryValues,
+) {
+    let field = |offset: usize| buffer_helper.value() + offset as u64;
+
+    if let Ok(value) = process.read::<f64>(field(offset_of!(BufferV1, file_seconds))) {
+        update_pair(value, &mut memory_values.file_seconds);
+    }
+    if let Ok(value) = process.read::<f64>(field(offset_of!(BufferV1, file_minutes))) {
+        update_pair(value, &mut memory_values.file_minutes);
+    }
+    if let Ok(value) = process.read::<f64>(field(offset_of!(BufferV1, level_seconds))) {
+        update_pair(value, &mut memory_values.level_seconds);
+    }
+    if let Ok(value) = process.read::<f64>(field(offset_of!(BufferV1, level_minutes))) {
+        update_pair(value, &mut memory_values.level_minutes);
+    }
+    if let Ok(value) =
+        process.read::<ArrayCString<ROOM_NAME_SIZE_CAP>>(field(offset_of!(BufferV1, room_name)))
+    {
+        update_pair(value, &mut memory_values.room_name);
+    }
+    if let Ok(value) = process.read::<u8>(field(offset_of!(BufferV1, end_of_level))) {
+        update_pair(value, &mut memory_values.end_of_level);
+    }
+    if let Ok(value) = process.read::<u8>(field(offset_of!(BufferV1, boss_hp))) {
+        update_pair(value, &mut memory_values.boss_hp);
+    }
+}
+
+/**
+ * display a count as "collected/total"
+ */
+fn set_variable_count(variable_name: &str, count: u32, total: u32) {
+    let mut buffer = itoa::Buffer::new();
+    let mut text = ArrayString::<24>::new();
+    text.push_str(buffer.format(count));
+    text.push('/');
+    text.push_str(buffer.format(total));
+    asr::timer::set_variable(variable_name, &text);
+}
+
+/**
+ * display the secrets found in the current level, if it has any, and in the run
+ */
+pub fn display_secrets((level, run): (Option<u32>, u32)) {
+    let level_count = room_names::COLLECTIBLE_LEVEL_COUNT as u32;
+    set_variable_count(
+        "Secrets",
+        level.unwrap_or_default(),
+        room_names::SECRETS_PER_LEVEL,
+    );
+    set_variable_count(
+        "Run Secrets",
+        run,
+        room_names::SECRETS_PER_LEVEL * level_count,
+    );
+}
+
+/**
+ * format a time as m:ss.cc, or s.cc under a minute, with a + or - in front for deltas
+ */
+pub fn format_time(seconds: f64, signed: bool) -> ArrayString<32> {
+    let mut text = ArrayString::new();
+    if signed {
+        text.push(if seconds < 0.0 { '-' } else { '+' });
+    }
+    // rounded by hand, core has no f64::round
+    let hundredths = (seconds.abs() * 100.0 + 0.5) as u64;
+    let (minutes, seconds, hundredths) =
+        (hundredths / 6000, hundredths / 100 % 60, hundredths % 100);
+    let mut buffer = itoa::Buffer::new();
+    if minutes > 0 {
+        text.push_str(buffer.format(minutes));
+        text.push(':');
+        if seconds < 10 {
+            text.push('0');
+        }
+    }
+    text.push_str(buffer.format(seconds));
+    text.push('.');
+    if hundredths < 10 {
+        text.push('0');
+    }
+    text.push_str(buffer.format(hundredths));
+    text
+}
+
+fn set_variable_time(variable_name: &str, seconds: Option<f64>, signed: bool) {
+    match seconds {
+        Some(seconds) => asr::timer::set_variable(variable_name, &format_time(seconds, signed)),
+        None => asr::timer::set_variable(variable_name, "-"),
+    }
+}
+
+/**
+ * display the best times of practice mode for where the player is
+ */
+pub fn display_practice(practice: &Practice) {
+    set_variable_time("Best For This Room", practice.best_for_room(), false);
+    set_variable_time("Best For This Level", practice.best_for_level(), false);
+    set_variable_time("Delta vs Best", practice.delta_vs_best(), true);
+    set_variable_time(
+        "Delta At Last Room Change",
+        practice.delta_at_room_change(),
+        true,
+    );
+    set_variable_time(
+        "Predicted Level Time",
+        practice.predicted_level_time(),
+        false,
+    );
+}
+
+/**
+ * display how many reads the last tick needed
+ */
+pub fn display_tick_cost(reads: u32) {
+    set_variable_int("Reads per tick", reads);
+}
+
+/**
+ * display the values read by refresh_mem_values in the variable view of livesplit
+ */
+pub fn display_mem_values(memory_addresses: &MemoryAddresses, memory_values: &MemoryValues) {
+    set_variable_int("Room ID", memory_values.room_id.current);
+
+    if memory_addresses.buffer_helper.is_some() {
+        set_variable_cstring("Game Version", &memory_values.game_version.current);
+        set_variable_f64("File Seconds", memory_values.file_seconds.current);
+        set_variable_f64("File Minutes", memory_values.file_minutes.current);
+        set_variable_f64("Level Seconds", memory_values.level_seconds.current);
+        set_variable_f64("Level Minutes", memory_values.level_minutes.current);
+        set_variable_cstring("Room Name (Buffer)", &memory_values.room_name.current);
+        set_variable_int("End Fade Exists", memory_values.end_of_level.current);
+        set_variable_int("Boss HP", memory_values.boss_hp.current);
+    } else {
+        set_variable_cstring("Room Name (GM Array)", &memory_values.room_name.current);
+    }
+}
@@ -0,0 +1,277 @@
+use asr::arrayvec::{ArrayString, ArrayVec};
+use asr::itoa;
+use asr::settings::Map;
+use asr::string::ArrayCString;
+
+use crate::memory::ROOM_NAME_SIZE_CAP;
+use crate::room_names::{self, Level};
+use crate::MemoryValues;
+
+// every practice time is in a map of its own under this key, so they can be forgotten at once
+const PRACTICE_TIMES_KEY: &str = "practice_times";
+const BEST_ROOM_KEY: &str = "practice_room_";
+const BEST_LEVEL_KEY: &str = "practice_level_";
+const REFERENCE_KEY: &str = "reference_";
+// the longest prefix plus two room names and a visit number
+const KEY_SIZE_CAP: usize = 2 * ROOM_NAME_SIZE_CAP + 16;
+// rooms entered in one attempt at a level, escapes go back through most of the level
+const ROOM_ENTRIES_CAPACITY: usize = 64;
+
+/**
+ * Where best times are kept between sessions, the settings map in the splitter and a list in tests
+ */
+pub trait BestTimes {
+    fn get(&mut self, key: &str) -> Option<f64>;
+    fn set(&mut self, key: &str, seconds: f64);
+}
+
+/**
+ * Best times stored as numbers in a map inside the settings map, so they are saved with the layout.
+ * The map is loaded on the first get or set and written back by store, once per tick at most
+ */
+pub struct SettingsMapBestTimes {
+    times: Option<Map>,
+    changed: bool,
+}
+
+impl SettingsMapBestTimes {
+    pub fn new() -> Self {
+        Self {
+            times: None,
+            changed: false,
+        }
+    }
+
+    fn times(&mut self) -> &Map {
+        self.times.get_or_insert_with(|| {
+            Map::load()
+                .get(PRACTICE_TIMES_KEY)
+                .and_then(|value| value.get_map())
+                .unwrap_or_else(Map::new)
+        })
+    }
+
+    /**
+     * Save the times set since the map was loaded, the next get loads it again
+     */
+    pub fn store(&mut self) {
+        if let (Some(times), true) = (self.times.take(), self.changed) {
+            // loaded again right before storing, so options changed in the GUI meanwhile aren't undone
+            let settings_map = Map::load();
+            settings_map.insert(PRACTICE_TIMES_KEY, &times);
+            settings_map.store();
+        }
+        self.changed = false;
+    }
+
+    /**
+     * Forget every best time and reference run
+     */
+    pub fn clear(&mut self) {
+        self.times = Some(Map::new());
+        self.changed = true;
+        self.store();
+    }
+}
+
+impl BestTimes for SettingsMapBestTimes {
+    fn get(&mut self, key: &str) -> Option<f64> {
+        self.times().get(key).and_then(|value| value.get_f64())
+    }
+
+    fn set(&mut self, key: &str, seconds: f64) {
+        self.times().insert(key, seconds);
+        self.changed = true;
+    }
+}
+
+fn best_key(prefix: &str, name: &str) -> ArrayString<KEY_SIZE_CAP> {
+    let mut key = ArrayString::new();
+    key.push_str(prefix);
+    let _ = key.try_push_str(name);
+    key
+}
+
+/**
+ * The key of the level time a room was entered at in the reference run of a level,
+ * visit counts the times the room was entered before in that run
+ */
+fn reference_key(starting_room: &str, room: &str, visit: usize) -> ArrayString<KEY_SIZE_CAP> {
+    let mut key = best_key(REFERENCE_KEY, starting_room);
+    let _ = key.try_push('_');
+    let _ = key.try_push_str(room);
+    let _ = key.try_push('_');
+    let _ = key.try_push_str(itoa::Buffer::new().format(visit));
+    key
+}
+
+/**
+ * Times every room and level played from the level timer and keeps the best ones.
+ *
+ * A room's time is the level time from entering it to leaving it, a level's time is the level time on the results screen.
+ * Restarting or dying puts the level timer back, the room being played then isn't timed.
+ *
+ * The attempt that sets a level's best time becomes its reference run: the level time every room was entered at is saved,
+ * and later attempts are compared with it on every room change
+ */
+pub struct Practice {
+    level: Level,
+    room: ArrayCString<ROOM_NAME_SIZE_CAP>,
+    room_entered_at: Option<f64>, // level time when the room was entered, None if it can't be timed
+    best_for_room: Option<f64>,
+    best_for_level: Option<f64>,
+    delta_vs_best: Option<f64>, // of the last room timed, negative when faster
+    // the rooms entered since the level started and the level time they were entered at
+    entries: ArrayVec<(ArrayCString<ROOM_NAME_SIZE_CAP>, f64), ROOM_ENTRIES_CAPACITY>,
+    delta_at_room_change: Option<f64>, // against the reference run, negative when ahead
+    predicted_level_time: Option<f64>,
+}
+
+impl Practice {
+    pub fn new() -> Self {
+        Self {
+            level: Level::Unknown,
+            room: ArrayCString::new(),
+            room_entered_at: None,
+            best_for_room: None,
+            best_for_level: None,
+            delta_vs_best: None,
+            entries: ArrayVec::new(),
+            delta_at_room_change: None,
+            predicted_level_time: None,
+        }
+    }
+
+    pub fn best_for_room(&self) -> Option<f64> {
+        self.best_for_room
+    }
+
+    pub fn best_for_level(&self) -> Option<f64> {
+        self.best_for_level
+    }
+
+    pub fn delta_vs_best(&self) -> Option<f64> {
+        self.delta_vs_best
+    }
+
+    pub fn delta_at_room_change(&self) -> Option<f64> {
+        self.delta_at_room_change
+    }
+
+    pub fn predicted_level_time(&self) -> Option<f64> {
+        self.predicted_level_time
+    }
+
+    /**
+     * How often a room was entered before in this attempt
+     */
+    fn visits(
+        entries: &[(ArrayCString<ROOM_NAME_SIZE_CAP>, f64)],
+        room: &ArrayCString<ROOM_NAME_SIZE_CAP>,
+    ) -> usize {
+        entries
+            .iter()
+            .filter(|(entered, _)| entered == room)
+            .count()
+    }
+
+    /**
+     * Forget the rooms of the last attempt, the first room of a new one is entered at 0
+     */
+    fn start_attempt(&mut self) {
+        self.entries.clear();
+        self.delta_at_room_change = None;
+        self.predicted_level_time = None;
+    }
+
+    /**
+     * Call every tick with the level the splitter says the player is in
+     */
+    pub fn tick(
+        &mut self,
+        mem_values: &MemoryValues,
+        level: Level,
+        best_times: &mut impl BestTimes,
+    ) {
+        let level_secs = mem_values.level_minutes.current * 60.0 + mem_values.level_seconds.current;
+        let level_secs_old = mem_values.level_minutes.old * 60.0 + mem_values.level_seconds.old;
+        if level_secs < level_secs_old {
+            self.room_entered_at = None;
+            // a restart, the escape going back through the first room keeps the attempt
+            self.start_attempt();
+        }
+        if !mem_values.room_name.changed() {
+            return;
+        }
+
+        if let (Some(entered_at), Ok(room)) = (self.room_entered_at, self.room.validate_utf8()) {
+            let room_time = level_secs - entered_at;
+            // without game time the level timer stays at 0 and nothing is timed
+            if room_time > 0.0 {
+                let key = best_key(BEST_ROOM_KEY, room);
+                let best = best_times.get(&key);
+                self.delta_vs_best = best.map(|best| room_time - best);
+                if best.is_none_or(|best| room_time < best) {
+                    best_times.set(&key, room_time);
+                }
+            }
+        }
+
+        // the level timer stops when the level ends, the results screen shows the final time
+        let finished_room = room_names::get_starting_room(&self.level);
+        if level == Level::ResultsScreen && finished_room != "-" && level_secs > 0.0 {
+            let key = best_key(BEST_LEVEL_KEY, finished_room);
+            if best_times.get(&key).is_none_or(|best| level_secs < best) {
+                best_times.set(&key, level_secs);
+                self.best_for_level = Some(level_secs);
+                // rooms of an older reference that this run didn't enter stay in the map, a route change can compare with them
+                for (index, (room, entered_at)) in self.entries.iter().enumerate() {
+                    if let Ok(name) = room.validate_utf8() {
+                        let visit = Self::visits(&self.entries[..index], room);
+                        best_times.set(&reference_key(finished_room, name, visit), *entered_at);
+                    }
+                }
+            }
+            self.entries.clear();
+        }
+
+        // the best for the last level stays shown outside of levels
+        let in_level = room_names::get_starting_room(&level) != "-";
+        if in_level && level != self.level {
+            self.best_for_level = best_times.get(&best_key(
+                BEST_LEVEL_KEY,
+                room_names::get_starting_room(&level),
+            ));
+        }
+
+        let starting_room = room_names::get_starting_room(&level);
+        let room = &mem_values.room_name.current;
+        if in_level && level != self.level {
+            self.start_attempt();
+        }
+        // the first room of an attempt is entered at 0, coming back through it in the escape is compared
+        let first_room = self.entries.is_empty() && room.matches(starting_room);
+        if let (true, Ok(name)) = (
+            in_level && level_secs > 0.0 && !first_room,
+            room.validate_utf8(),
+        ) {
+            // without game time the level timer stays at 0 and there is nothing to compare
+            let visit = Self::visits(&self.entries, room);
+            let reference = best_times.get(&reference_key(starting_room, name, visit));
+            self.delta_at_room_change = reference.map(|reference| level_secs - reference);
+            self.predicted_level_time = self
+                .delta_at_room_change
+                .zip(self.best_for_level)
+                .map(|(delta, best)| best + delta);
+            let _ = self.entries.try_push((*room, level_secs));
+        }
+
+        self.level = level;
+        self.room = mem_values.room_name.current;
+        self.room_entered_at = in_level.then_some(level_secs);
+        self.best_for_room = match self.room.validate_utf8() {
+            Ok(room) if in_level => best_times.get(&best_key(BEST_ROOM_KEY, room)),
+            _ => None,
+        };
+    }
+}
@@ -0,0 +1,304 @@
+//! Replays a trace written by the trace recorder through the splitter, on the host.
+
+use std::collections::HashMap;
+use std::format;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use asr::string::ArrayCString;
+use asr::timer::TimerState;
+use asr::watcher::Pair;
+
+use crate::custom_splits::{CustomSplit, CustomSplits};
+use crate::memory::ROOM_NAME_SIZE_CAP;
+use crate::room_names;
+use crate::route::Route;
+use crate::settings::{CampaignChoice, Settings, TimerMode};
+use crate::splitter::{SplitterState, TimerAction};
+use crate::trace::{action_code, TRACE_HEADER};
+use crate::MemoryValues;
+
+/**
+ * Stands in for livesplit's timer, which ignores actions that don't fit its current state
+ */
+pub struct TimerSim {
+    pub state: TimerState,
+}
+
+impl Default for TimerSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimerSim {
+    pub fn new() -> Self {
+        Self {
+            state: TimerState::NotRunning,
+        }
+    }
+
+    /**
+     * returns true if livesplit would have done something with the action
+     */
+    pub fn apply(&mut self, action: TimerAction) -> bool {
+        match (action, self.state) {
+            (
+                TimerAction::SetGameTime(_)
+                | TimerAction::PauseGameTime
+                | TimerAction::ResumeGameTime,
+                _,
+            ) => true,
+            (TimerAction::Start, TimerState::NotRunning) => {
+                self.state = TimerState::Running;
+                true
+            }
+            (TimerAction::Split | TimerAction::SkipSplit, TimerState::Running) => true,
+            (TimerAction::Reset, TimerState::Running) => {
+                self.state = TimerState::NotRunning;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+struct TraceFrame {
+    tick: u32,
+    room_id: i32,
+    room: String,
+    file_secs: f64,
+    level_secs: f64,
+    end_of_level: u8,
+    boss_hp: u8,
+    timer_state: TimerState,
+    actions: String,
+}
+
+struct Trace {
+    settings: Settings,
+    // only in traces from games whose variables were found
+    custom_splits: CustomSplits,
+    route: Route,
+    game_time_available: bool,
+    frames: Vec<TraceFrame>,
+}
+
+fn parse_field<T: core::str::FromStr>(
+    fields: &mut core::str::SplitWhitespace,
+    name: &str,
+    line_number: usize,
+) -> Result<T, String> {
+    fields
+        .next()
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| format!("line {line_number}: missing or invalid {name}"))
+}
+
+fn parse_trace(text: &str) -> Result<Trace, String> {
+    // the trace usually comes from a log, skip anything before the header
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .skip_while(|(_, line)| !line.ends_with(TRACE_HEADER));
+    if lines.next().is_none() {
+        return Err(format!("no \"{TRACE_HEADER}\" header found"));
+    }
+
+    let mut settings = None;
+    let mut custom_splits = CustomSplits::default();
+    // traces from before routes could be picked don't have a route line
+    let mut route = Route::default();
+    let mut game_time_available = true;
+    let mut rooms = HashMap::new();
+    let mut frames = Vec::new();
+
+    for (line_number, line) in lines {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("settings") => {
+                // long settings are split over several lines, each adding to the previous ones
+                let mut parsed = settings
+                    .take()
+                    .unwrap_or_else(|| Settings::for_mode(TimerMode::FullGame));
+                for field in fields {
+                    let (key, value) = field
+                        .split_once('=')
+                        .ok_or_else(|| format!("line {line_number}: bad setting {field}"))?;
+                    if key == "mode" {
+                        let mode = TimerMode::from_name(value)
+                            .ok_or_else(|| format!("line {line_number}: unknown mode {value}"))?;
+                        parsed.timer_mode = Pair {
+                            old: mode,
+                            current: mode,
+                        };
+                    } else if key == "campaign" {
+                        parsed.campaign = CampaignChoice::from_name(value).ok_or_else(|| {
+                            format!("line {line_number}: unknown campaign {value}")
+                        })?;
+                    } else if !parsed.set_flag(key, value == "1") {
+                        return Err(format!("line {line_number}: unknown setting {key}"));
+                    }
+                }
+                settings = Some(parsed);
+            }
+            Some("custom_split") => {
+                let text = line.trim_start_matches("custom_split");
+                let split = CustomSplit::parse(text)
+                    .map_err(|error| format!("line {line_number}: {error}"))?;
+                custom_splits.push(split);
+            }
+            Some("route") => {
+                let text = line.trim_start_matches("route").trim();
+                route =
+                    Route::parse(text).map_err(|error| format!("line {line_number}: {error}"))?;
+            }
+            Some("game_time") => {
+                game_time_available =
+                    parse_field::<u8>(&mut fields, "game_time", line_number)? == 1;
+            }
+            Some("room") => {
+                let index: u16 = parse_field(&mut fields, "room index", line_number)?;
+                let name = fields.next().unwrap_or_default().to_string();
+                rooms.insert(index, name);
+            }
+            Some("frame") => {
+                let tick = parse_field(&mut fields, "tick", line_number)?;
+                let room_id = parse_field(&mut fields, "room id", line_number)?;
+                let room_index: u16 = parse_field(&mut fields, "room index", line_number)?;
+                frames.push(TraceFrame {
+                    tick,
+                    room_id,
+                    room: rooms.get(&room_index).cloned().unwrap_or_default(),
+                    file_secs: parse_field(&mut fields, "file seconds", line_number)?,
+                    level_secs: parse_field(&mut fields, "level seconds", line_number)?,
+                    end_of_level: parse_field(&mut fields, "end fade", line_number)?,
+                    boss_hp: parse_field(&mut fields, "boss hp", line_number)?,
+                    timer_state: match fields.next() {
+                        Some("R") => TimerState::Running,
+                        Some("P") => TimerState::Paused,
+                        Some("E") => TimerState::Ended,
+                        _ => TimerState::NotRunning,
+                    },
+                    actions: fields.next().unwrap_or("-").trim_matches('-').to_string(),
+                });
+            }
+            // anything else in the log isn't part of the trace
+            _ => {}
+        }
+    }
+
+    Ok(Trace {
+        settings: settings.ok_or("the trace has no settings line")?,
+        custom_splits,
+        route,
+        game_time_available,
+        frames,
+    })
+}
+
+pub fn room_cstring(name: &str) -> ArrayCString<ROOM_NAME_SIZE_CAP> {
+    let mut bytes = [0; ROOM_NAME_SIZE_CAP];
+    let length = name.len().min(ROOM_NAME_SIZE_CAP - 1);
+    bytes[..length].copy_from_slice(&name.as_bytes()[..length]);
+    bytemuck::checked::cast(bytes)
+}
+
+fn update_pair<T: Copy>(new_value: T, pair: &mut Pair<T>) {
+    pair.old = pair.current;
+    pair.current = new_value;
+}
+
+fn format_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:06.3}", minutes, seconds - minutes * 60.0)
+}
+
+fn action_name(action: TimerAction) -> &'static str {
+    match action {
+        TimerAction::Start => "start",
+        TimerAction::Split => "split",
+        TimerAction::SkipSplit => "skip split",
+        TimerAction::Reset => "reset",
+        TimerAction::SetGameTime(_) => "game time",
+        TimerAction::PauseGameTime => "pause game time for a load",
+        TimerAction::ResumeGameTime => "resume game time",
+    }
+}
+
+/**
+ * Run the splitter over every frame of the trace and describe what it did, one line per event.
+ * Ticks where the replayed actions don't match the ones stored in the trace are marked, those are the interesting ones.
+ */
+pub fn replay(text: &str) -> Result<Vec<String>, String> {
+    let trace = parse_trace(text)?;
+    let mut splitter = SplitterState::new(trace.game_time_available);
+    splitter.set_custom_splits(trace.custom_splits);
+    splitter.set_route(trace.route);
+    let mut timer = TimerSim::new();
+    let mut values = MemoryValues::default();
+    let mut game_time: Option<f64> = None;
+    let mut output = Vec::new();
+
+    output.push(format!(
+        "replaying {} frames, mode {}",
+        trace.frames.len(),
+        trace.settings.timer_mode.current.name()
+    ));
+
+    for frame in &trace.frames {
+        update_pair(frame.room_id, &mut values.room_id);
+        update_pair(room_cstring(&frame.room), &mut values.room_name);
+        update_pair(0.0, &mut values.file_minutes);
+        update_pair(frame.file_secs, &mut values.file_seconds);
+        update_pair(0.0, &mut values.level_minutes);
+        update_pair(frame.level_secs, &mut values.level_seconds);
+        update_pair(frame.end_of_level, &mut values.end_of_level);
+        update_pair(frame.boss_hp, &mut values.boss_hp);
+
+        let room_old = values.room_name.old.validate_utf8().unwrap_or("?");
+        let transition = if values.room_name.changed() {
+            format!("{room_old} -> {}", frame.room)
+        } else {
+            frame.room.clone()
+        };
+
+        // the splitter sees what livesplit's timer was really doing when the trace was recorded
+        timer.state = frame.timer_state;
+        let actions = splitter.tick(&values, &trace.settings, timer.state);
+        let replayed_codes: String = actions.iter().filter_map(action_code).collect();
+
+        for action in actions {
+            let ignored = !timer.apply(action);
+            if let TimerAction::SetGameTime(seconds) = action {
+                if game_time.is_some_and(|previous| seconds < previous) {
+                    output.push(format!(
+                        "tick {}: game time went back to {} ({transition})",
+                        frame.tick,
+                        format_time(seconds)
+                    ));
+                }
+                game_time = Some(seconds);
+                continue;
+            }
+            output.push(format!(
+                "tick {}: {}{} at {} [{}] ({transition})",
+                frame.tick,
+                action_name(action),
+                if ignored { " (ignored by timer)" } else { "" },
+                format_time(game_time.unwrap_or(frame.file_secs)),
+                room_names::get_full_level_name(&splitter.current_level(), splitter.campaign()),
+            ));
+        }
+
+        if replayed_codes != frame.actions {
+            output.push(format!(
+                "tick {}: MISMATCH recorded \"{}\" but replayed \"{}\"",
+                frame.tick, frame.actions, replayed_codes
+            ));
+        }
+    }
+
+    Ok(output)
+}
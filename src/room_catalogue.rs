@@ -0,0 +1,61 @@
+use asr::arrayvec::ArrayVec;
+use asr::print_message;
+use asr::string::ArrayCString;
+
+use crate::memory::ROOM_NAME_SIZE_CAP;
+use crate::room_names::{self, Level};
+
+// unknown rooms reported once each, a patch adding more than this is reported by the ones that fit
+const REPORTED_ROOMS_CAPACITY: usize = 32;
+
+// rooms outside of any level: menus, loading screens and the cutscene after pizzaface
+const OTHER_ROOMS: [&str; 4] = [
+    "Finalintro",
+    "hub_loadingscreen",
+    "Loadiingroom",
+    "boss_pizzafacefinale",
+];
+
+/**
+ * Returns true if the room belongs to a level the splitter knows, or is one of the rooms outside of levels
+ */
+pub fn is_known_room(room_name: &str) -> bool {
+    OTHER_ROOMS.contains(&room_name)
+        || room_names::get_current_level(room_name, Level::Unknown) != Level::Unknown
+}
+
+/**
+ * Reports rooms that aren't in any known level, once per room, so a patch adding a level or a menu shows up right away
+ */
+pub struct UnknownRooms {
+    reported: ArrayVec<ArrayCString<ROOM_NAME_SIZE_CAP>, REPORTED_ROOMS_CAPACITY>,
+}
+
+impl UnknownRooms {
+    pub fn new() -> Self {
+        Self {
+            reported: ArrayVec::new(),
+        }
+    }
+
+    /**
+     * Call when the room changes, returns true the first time an unknown room is seen
+     */
+    pub fn check(&mut self, room_name: &ArrayCString<ROOM_NAME_SIZE_CAP>) -> bool {
+        let Ok(name) = room_name.validate_utf8() else {
+            return false;
+        };
+        // nothing is read before the game is ready
+        if name.is_empty() || is_known_room(name) || self.reported.contains(room_name) {
+            return false;
+        }
+        if self.reported.try_push(*room_name).is_err() {
+            return false;
+        }
+
+        print_message("Room not in any known level, please report it:");
+        print_message(name);
+        asr::timer::set_variable("Unknown Room", name);
+        true
+    }
+}
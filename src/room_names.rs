@@ -1,4 +1,4 @@
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Level {
     Hub,
     F1Tutorial,
@@ -34,10 +34,120 @@ pub enum Level {
     Unknown,
 }
 
+/**
+ * Who the save is played as, the noise update added a campaign for the noise and a mode switching between both
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Campaign {
+    #[default]
+    Unknown,
+    Peppino,
+    Noise,
+    Swap,
+}
+
+impl Campaign {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Campaign::Unknown => "Unknown",
+            Campaign::Peppino => "Peppino",
+            Campaign::Noise => "The Noise",
+            Campaign::Swap => "Swap Mode",
+        }
+    }
+}
+
+/**
+ * The campaign a level can only be played in, each character has their own tutorial
+ */
+pub fn campaign_of_level(level: &Level) -> Option<Campaign> {
+    match level {
+        Level::F1Tutorial => Some(Campaign::Peppino),
+        Level::F1TutorialNoise => Some(Campaign::Noise),
+        _ => None,
+    }
+}
+
+/**
+ * Returns true when leaving the pizzaface fight the way the noise does, his fight ends without the boss hp reaching 0.
+ * Peppino only takes this way when giving up on the fight
+ */
+pub fn noise_final_boss_exit(old_room: &str, current_room: &str) -> bool {
+    old_room == "boss_pizzaface" && current_room == "boss_pizzafacehub"
+}
+
+/**
+ * Rooms with a name of their own that decide the level, sorted by name for a binary search.
+ * An exact name wins over any prefix
+ */
+const LEVEL_EXACT_ROOMS: [(&str, Level); 7] = [
+    ("boss_noise", Level::Noise),
+    ("boss_pepperman", Level::Pepperman),
+    ("boss_pizzaface", Level::PizzaFace),
+    ("boss_pizzafacehub", Level::Hub),
+    ("boss_vigilante", Level::Vigilante),
+    ("rank_room", Level::ResultsScreen),
+    ("tower_finalhallway", Level::F5CrumblingTower),
+];
+
+/**
+ * The start of the room names of each level, sorted by prefix. The longest prefix a room starts with wins,
+ * so tower_tutorial1N goes to the noise tutorial before tower_tutorial and tower_ are tried
+ */
+const LEVEL_ROOM_PREFIXES: [(&str, Level); 27] = [
+    ("badland_", Level::F2OreganoDesert),
+    ("boss_fakepep", Level::Fake),
+    ("chateau_", Level::F5Pizzascare),
+    ("dungeon_", Level::F1BloodsauceDungeon),
+    ("entrance_", Level::F1JohnGutter),
+    ("farm_", Level::F2FunFarm),
+    ("forest_", Level::F3GnomeForest),
+    ("freezer_", Level::F4Refrigerator),
+    ("graveyard_", Level::F2Wasteyard),
+    ("industrial_", Level::F4PeppibotFactory),
+    ("kidsparty_", Level::F5DMAS),
+    ("medieval_", Level::F1Pizzascape),
+    ("minigolf_", Level::F3Golf),
+    ("plage_", Level::F3CrustCove),
+    ("ruin_", Level::F1AncientCheese),
+    ("saloon_", Level::F2FastfoodSaloon),
+    ("secret_entrance", Level::SecretsOfTheWorld),
+    ("sewer_", Level::F4OhShit),
+    ("space_", Level::F3DeepDish9),
+    ("street_", Level::F4ThePigCity),
+    ("tower_", Level::Hub),
+    ("tower_tutorial", Level::F1Tutorial),
+    ("tower_tutorial1N", Level::F1TutorialNoise),
+    ("tower_tutorial2N", Level::F1TutorialNoise),
+    ("tower_tutorial3N", Level::F1TutorialNoise),
+    ("trickytreat", Level::TrickyTreat),
+    ("war_", Level::F5War),
+];
+
+/**
+ * The level a room belongs to on its own, from the tables above
+ */
+fn level_of_room(room_name: &str) -> Level {
+    if let Ok(index) = LEVEL_EXACT_ROOMS.binary_search_by(|(name, _)| (*name).cmp(room_name)) {
+        return LEVEL_EXACT_ROOMS[index].1;
+    }
+
+    // every prefix of the room sorts before it and starts with the same letter
+    let end = LEVEL_ROOM_PREFIXES.partition_point(|(prefix, _)| *prefix <= room_name);
+    let first_letter = room_name.bytes().next();
+    LEVEL_ROOM_PREFIXES[..end]
+        .iter()
+        .rev()
+        .take_while(|(prefix, _)| prefix.bytes().next() == first_letter)
+        .filter(|(prefix, _)| room_name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(Level::Unknown, |(_, level)| *level) // where did you go?
+}
+
 pub fn get_current_level(room_name: &str, prev_level: Level) -> Level {
     // special cases for rooms that overlap in multiple levels
     if prev_level == Level::F5CrumblingTower
-        && room_name.contains("tower_")
+        && room_name.starts_with("tower_")
         && room_name != "tower_pizzafacehall"
     {
         return Level::F5CrumblingTower;
@@ -47,45 +157,15 @@ pub fn get_current_level(room_name: &str, prev_level: Level) -> Level {
         return Level::SecretsOfTheWorld;
     }
 
-    match room_name {
-        "tower_finalhallway" => Level::F5CrumblingTower,
-        x if x.contains("tower_tutorial1N")
-            || x.contains("tower_tutorial2N")
-            || x.contains("tower_tutorial3N") =>
-        {
-            Level::F1TutorialNoise
-        }
-        x if x.contains("tower_tutorial") => Level::F1Tutorial,
-        x if x.contains("tower_") || x == "boss_pizzafacehub" => Level::Hub,
-        x if x.contains("entrance_") => Level::F1JohnGutter,
-        x if x.contains("medieval_") => Level::F1Pizzascape,
-        x if x.contains("ruin_") => Level::F1AncientCheese,
-        x if x.contains("dungeon_") => Level::F1BloodsauceDungeon,
-        "boss_pepperman" => Level::Pepperman,
-        x if x.contains("badland_") => Level::F2OreganoDesert,
-        x if x.contains("graveyard_") => Level::F2Wasteyard,
-        x if x.contains("farm_") => Level::F2FunFarm,
-        x if x.contains("saloon_") => Level::F2FastfoodSaloon,
-        "boss_vigilante" => Level::Vigilante,
-        x if x.contains("plage_") => Level::F3CrustCove,
-        x if x.contains("forest_") => Level::F3GnomeForest,
-        x if x.contains("space_") => Level::F3DeepDish9,
-        x if x.contains("minigolf_") => Level::F3Golf,
-        "boss_noise" => Level::Noise,
-        x if x.contains("street_") => Level::F4ThePigCity,
-        x if x.contains("industrial_") => Level::F4PeppibotFactory,
-        x if x.contains("sewer_") => Level::F4OhShit,
-        x if x.contains("freezer_") => Level::F4Refrigerator,
-        x if x.contains("boss_fakepep") => Level::Fake,
-        x if x.contains("secret_entrance") => Level::SecretsOfTheWorld,
-        x if x.contains("trickytreat") => Level::TrickyTreat,
-        x if x.contains("chateau_") => Level::F5Pizzascare,
-        x if x.contains("kidsparty_") => Level::F5DMAS,
-        x if x.contains("war_") => Level::F5War,
-        "boss_pizzaface" => Level::PizzaFace,
-        "rank_room" => Level::ResultsScreen,
-        _ => Level::Unknown, // where did you go?
-    }
+    level_of_room(room_name)
+}
+
+/**
+ * The exact and the prefix table, for checking they are sorted
+ */
+#[cfg(test)]
+pub fn level_room_tables() -> [&'static [(&'static str, Level)]; 2] {
+    [&LEVEL_EXACT_ROOMS, &LEVEL_ROOM_PREFIXES]
 }
 
 pub fn get_starting_room<'a>(level: &Level) -> &'a str {
@@ -159,6 +239,215 @@ pub fn full_game_split_unlock_rooms(current_room: &str) -> bool {
     .contains(&current_room)
 }
 
+/**
+ * Returns true for the John pillar room of a level, reaching it is when the escape (Pizza Time) starts
+ */
+pub fn escape_start_rooms(current_room: &str) -> bool {
+    [
+        "entrance_10",
+        "medieval_10",
+        "ruin_11",
+        "dungeon_10",
+        "badland_9",
+        "graveyard_6",
+        "farm_11",
+        "saloon_6",
+        "plage_cavern2",
+        "forest_john",
+        "space_9",
+        "minigolf_8",
+        "street_john",
+        "sewer_8",
+        "industrial_5",
+        "freezer_escape1",
+        "chateau_9",
+        "kidsparty_john",
+    ]
+    .contains(&current_room)
+}
+
+/**
+ * Returns true for the rooms the game shows while loading, game time without the buffer helper pauses in them
+ */
+pub fn loading_rooms(current_room: &str) -> bool {
+    ["hub_loadingscreen", "Loadiingroom"].contains(&current_room)
+}
+
+pub const SECRETS_PER_LEVEL: u32 = 3;
+
+/**
+ * Which of the three secrets of a level a room is, from 0, for rooms named like entrance_secret1 to entrance_secret3
+ */
+pub fn secret_index(current_room: &str) -> Option<u8> {
+    let (_, number) = current_room.rsplit_once("_secret")?;
+    match number {
+        "1" => Some(0),
+        "2" => Some(1),
+        "3" => Some(2),
+        _ => None,
+    }
+}
+
+/**
+ * The checkpoint rooms of a level that get a subsplit: the secret rooms and the John pillar room where the escape starts.
+ * Levels without checkpoints (bosses, tutorial, the tower itself) return an empty list
+ */
+pub fn checkpoint_rooms(level: &Level) -> &'static [&'static str] {
+    match level {
+        Level::F1JohnGutter => &[
+            "entrance_secret1",
+            "entrance_secret2",
+            "entrance_secret3",
+            "entrance_10",
+        ],
+        Level::F1Pizzascape => &[
+            "medieval_secret1",
+            "medieval_secret2",
+            "medieval_secret3",
+            "medieval_10",
+        ],
+        Level::F1AncientCheese => &["ruin_secret1", "ruin_secret2", "ruin_secret3", "ruin_11"],
+        Level::F1BloodsauceDungeon => &[
+            "dungeon_secret1",
+            "dungeon_secret2",
+            "dungeon_secret3",
+            "dungeon_10",
+        ],
+        Level::F2OreganoDesert => &[
+            "badland_secret1",
+            "badland_secret2",
+            "badland_secret3",
+            "badland_9",
+        ],
+        Level::F2Wasteyard => &[
+            "graveyard_secret1",
+            "graveyard_secret2",
+            "graveyard_secret3",
+            "graveyard_6",
+        ],
+        Level::F2FunFarm => &["farm_secret1", "farm_secret2", "farm_secret3", "farm_11"],
+        Level::F2FastfoodSaloon => &[
+            "saloon_secret1",
+            "saloon_secret2",
+            "saloon_secret3",
+            "saloon_6",
+        ],
+        Level::F3CrustCove => &[
+            "plage_secret1",
+            "plage_secret2",
+            "plage_secret3",
+            "plage_cavern2",
+        ],
+        Level::F3GnomeForest => &[
+            "forest_secret1",
+            "forest_secret2",
+            "forest_secret3",
+            "forest_john",
+        ],
+        Level::F3Golf => &[
+            "minigolf_secret1",
+            "minigolf_secret2",
+            "minigolf_secret3",
+            "minigolf_8",
+        ],
+        Level::F3DeepDish9 => &["space_secret1", "space_secret2", "space_secret3", "space_9"],
+        Level::F4ThePigCity => &[
+            "street_secret1",
+            "street_secret2",
+            "street_secret3",
+            "street_john",
+        ],
+        Level::F4OhShit => &["sewer_secret1", "sewer_secret2", "sewer_secret3", "sewer_8"],
+        Level::F4PeppibotFactory => &[
+            "industrial_secret1",
+            "industrial_secret2",
+            "industrial_secret3",
+            "industrial_5",
+        ],
+        Level::F4Refrigerator => &[
+            "freezer_secret1",
+            "freezer_secret2",
+            "freezer_secret3",
+            "freezer_escape1",
+        ],
+        Level::F5Pizzascare => &[
+            "chateau_secret1",
+            "chateau_secret2",
+            "chateau_secret3",
+            "chateau_9",
+        ],
+        Level::F5DMAS => &[
+            "kidsparty_secret1",
+            "kidsparty_secret2",
+            "kidsparty_secret3",
+            "kidsparty_john",
+        ],
+        Level::F5War => &["war_secret1", "war_secret2", "war_secret3"],
+        _ => &[],
+    }
+}
+
+pub const COLLECTIBLE_LEVEL_COUNT: usize = 19;
+
+/**
+ * The levels with 5 toppins, a treasure and 3 secrets to find, the tower, bosses and tutorials have none
+ */
+pub fn collectible_levels() -> &'static [Level; COLLECTIBLE_LEVEL_COUNT] {
+    &[
+        Level::F1JohnGutter,
+        Level::F1Pizzascape,
+        Level::F1AncientCheese,
+        Level::F1BloodsauceDungeon,
+        Level::F2OreganoDesert,
+        Level::F2Wasteyard,
+        Level::F2FunFarm,
+        Level::F2FastfoodSaloon,
+        Level::F3CrustCove,
+        Level::F3GnomeForest,
+        Level::F3Golf,
+        Level::F3DeepDish9,
+        Level::F4ThePigCity,
+        Level::F4OhShit,
+        Level::F4PeppibotFactory,
+        Level::F4Refrigerator,
+        Level::F5Pizzascare,
+        Level::F5DMAS,
+        Level::F5War,
+    ]
+}
+
+/**
+ * The levels and bosses of a full game run in the order they are finished, one split each
+ */
+pub fn full_game_route() -> &'static [Level] {
+    &[
+        Level::F1JohnGutter,
+        Level::F1Pizzascape,
+        Level::F1AncientCheese,
+        Level::F1BloodsauceDungeon,
+        Level::Pepperman,
+        Level::F2OreganoDesert,
+        Level::F2Wasteyard,
+        Level::F2FunFarm,
+        Level::F2FastfoodSaloon,
+        Level::Vigilante,
+        Level::F3CrustCove,
+        Level::F3GnomeForest,
+        Level::F3DeepDish9,
+        Level::F3Golf,
+        Level::Noise,
+        Level::F4ThePigCity,
+        Level::F4OhShit,
+        Level::F4PeppibotFactory,
+        Level::F4Refrigerator,
+        Level::Fake,
+        Level::F5Pizzascare,
+        Level::F5DMAS,
+        Level::F5War,
+        Level::PizzaFace,
+    ]
+}
+
 /**
  * Return true if it receives a room that should trigger a split, usually where the levels end
  */
@@ -197,7 +486,7 @@ pub fn full_game_split_rooms(exited_level: &str) -> bool {
     .contains(&exited_level)
 }
 
-pub fn get_full_level_name<'a>(level: &Level) -> &'a str {
+pub fn get_full_level_name<'a>(level: &Level, campaign: Campaign) -> &'a str {
     match level {
         Level::Hub => "Hub",
         Level::F1Tutorial => "Tutorial",
@@ -226,7 +515,12 @@ pub fn get_full_level_name<'a>(level: &Level) -> &'a str {
         Level::TrickyTreat => "Tricky Treat",
         Level::Pepperman => "Pepperman",
         Level::Vigilante => "The Vigilante",
-        Level::Noise => "The Noise/The Doise",
+        // the noise fights the doise in the same room
+        Level::Noise => match campaign {
+            Campaign::Peppino => "The Noise",
+            Campaign::Noise => "The Doise",
+            Campaign::Unknown | Campaign::Swap => "The Noise/The Doise",
+        },
         Level::Fake => "Fake Peppino",
         Level::PizzaFace => "Pizzaface",
         Level::ResultsScreen => "You did it! Pizza Tower",
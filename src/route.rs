@@ -0,0 +1,96 @@
+use asr::arrayvec::{ArrayString, ArrayVec};
+use asr::print_message;
+
+use crate::room_names::{self, Level};
+
+// read from the settings map, the full game route is used without it
+const ROUTE_KEY: &str = "route";
+// every level and boss of the game fits
+pub const ROUTE_CAPACITY: usize = 32;
+// a room name and a comma for every level
+pub const ROUTE_TEXT_CAP: usize = 1024;
+
+/**
+ * The levels and bosses the runner finishes, in order, one split each.
+ * Written as the rooms the levels start in separated by commas, "entrance_1, medieval_1, boss_pepperman"
+ */
+#[derive(Clone)]
+pub struct Route {
+    levels: ArrayVec<Level, ROUTE_CAPACITY>,
+    // the settings map entry the route was parsed from, empty for the full game route
+    text: ArrayString<ROUTE_TEXT_CAP>,
+}
+
+impl Default for Route {
+    fn default() -> Self {
+        Self {
+            levels: room_names::full_game_route().iter().copied().collect(),
+            text: ArrayString::new(),
+        }
+    }
+}
+
+impl Route {
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let mut levels = ArrayVec::new();
+        for room in text.split(',').map(str::trim) {
+            let level = room_names::get_current_level(room, Level::Unknown);
+            if matches!(level, Level::Unknown | Level::Hub | Level::ResultsScreen) {
+                return Err("Route has a room that isn't in a level or boss");
+            }
+            levels
+                .try_push(level)
+                .map_err(|_| "Route has too many levels")?;
+        }
+        Ok(Self {
+            levels,
+            text: ArrayString::from(text).map_err(|_| "Route is too long")?,
+        })
+    }
+
+    /**
+     * Read the route from the settings map, the full game route when it is missing or malformed.
+     * There is no text box in the settings GUI, so it is set in the layout or splits file
+     */
+    pub fn load() -> Self {
+        let mut route = Self::default();
+        route.reload();
+        route
+    }
+
+    /**
+     * Read the entry again and parse it if it changed since the last time,
+     * returns true if the route changed
+     */
+    pub fn reload(&mut self) -> bool {
+        let text = asr::settings::Map::load()
+            .get(ROUTE_KEY)
+            .and_then(|value| value.get_array_string::<ROUTE_TEXT_CAP>())
+            .and_then(|value| value.ok())
+            .unwrap_or_default();
+        if text == self.text {
+            return false;
+        }
+
+        *self = if text.trim().is_empty() {
+            Self::default()
+        } else {
+            Self::parse(&text).unwrap_or_else(|error| {
+                print_message(ROUTE_KEY);
+                print_message(error);
+                Self::default()
+            })
+        };
+        // a malformed entry isn't reported again until it changes
+        self.text = text;
+        true
+    }
+
+    pub fn get(&self, position: usize) -> Option<&Level> {
+        self.levels.get(position)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Level> {
+        self.levels.iter()
+    }
+}
@@ -2,6 +2,8 @@ use asr::settings::gui::Title;
 use asr::settings::Gui;
 use asr::watcher::Pair;
 
+use crate::room_names::Campaign;
+
 #[derive(Gui, Clone, Copy, PartialEq, core::cmp::Eq)]
 pub enum TimerMode {
     /// Full Game
@@ -15,6 +17,22 @@ pub enum TimerMode {
     IW,
 }
 
+/**
+ * The campaign the runner says they play, the game only shows it in the tutorials
+ */
+#[derive(Gui, Clone, Copy, PartialEq, core::cmp::Eq)]
+pub enum CampaignChoice {
+    /// Detect
+    #[default]
+    Detect,
+    /// Peppino
+    Peppino,
+    /// The Noise
+    Noise,
+    /// Swap Mode
+    Swap,
+}
+
 #[derive(Gui)]
 pub struct Settings {
     /// NOTE: Use "-livesplit" as a launch options to use game time.
@@ -30,6 +48,17 @@ pub struct Settings {
     /// Load recommended settings when switching mode
     pub timer_mode_load_defaults: bool,
 
+    #[default = true]
+    /// Remove loading screens from game time without "-livesplit"
+    ///
+    /// Without the launch option game time is real time, paused while the game shows a loading screen
+    pub game_time_remove_loads: bool,
+
+    /// Campaign
+    ///
+    /// Detect only knows the campaign from its tutorial, pick the character for runs that skip the tutorial and for swap mode. Decides the noise's final boss split and the Doise's name
+    pub campaign: CampaignChoice,
+
     /// Start Options
     _timer_mode_title: Title,
 
@@ -49,6 +78,12 @@ pub struct Settings {
     /// On starting a level
     pub start_new_il: bool,
 
+    #[default = false]
+    /// On starting any level, resetting a run of another level (IL practice)
+    ///
+    /// One IL layout for every level. Auto splitters can't rename segments, the Current IL variable shows the level being timed
+    pub start_any_il: bool,
+
     #[default = false]
     /// On exiting a level
     ///
@@ -68,6 +103,48 @@ pub struct Settings {
     /// All full game splits, including pizza face
     pub splits_level_end: bool,
 
+    #[default = false]
+    /// Only split on the next level of the route
+    ///
+    /// Level end splits follow the route, so revisiting a level doesn't split. The route is the full game level order unless the "route" entry is set in the layout or splits file, see the README. The other kinds of splits don't move the route, splits done by hand each pass one level of it
+    pub splits_route: bool,
+
+    #[default = false]
+    /// Allow the route to skip a level
+    ///
+    /// Finishing the level after the expected one skips the expected split and splits
+    pub splits_route_skips: bool,
+
+    #[default = false]
+    /// On starting the escape (Pizza Time)
+    ///
+    /// Splits when reaching the John pillar room of a level, for separate level and escape segments
+    pub splits_escape: bool,
+
+    #[default = false]
+    /// On taking a lap portal
+    ///
+    /// Splits at the start of lap 2 and every lap after it
+    pub splits_laps: bool,
+
+    #[default = false]
+    /// On reaching a level checkpoint
+    ///
+    /// Secret rooms and the escape start of each level, once per visit, for full game subsplits
+    pub splits_checkpoints: bool,
+
+    #[default = false]
+    /// On finding a secret
+    ///
+    /// Splits the first time each secret room of a level is entered in a run, for 100% runs
+    pub splits_secrets: bool,
+
+    #[default = false]
+    /// On custom room transitions
+    ///
+    /// The pairs can't be edited here, set custom_split_1 to custom_split_16 in the layout or splits file to "from_room -> to_room", medieval_* matches every room starting with medieval_. See the README
+    pub splits_custom: bool,
+
     #[default = false]
     /// On room change
     pub splits_rooms: bool,
@@ -92,6 +169,148 @@ pub struct Settings {
     #[default = true]
     /// On restarting a level
     pub reset_new_level: bool,
+
+    /// Practice
+    _practice_title: Title,
+
+    #[default = false]
+    /// Keep best times for every room and level
+    ///
+    /// Times rooms and levels with the level timer and saves the best ones with the layout, shown in the Best For This Room, Best For This Level and Delta vs Best variables. The run of a level's best time is kept as a reference, every room change shows the Delta At Last Room Change and the Predicted Level Time against it
+    pub practice_enable: bool,
+
+    #[default = false]
+    /// Forget every best time and reference run
+    ///
+    /// Unticks itself once they are forgotten
+    pub practice_reset: bool,
+
+    /// Debugging
+    _debug_title: Title,
+
+    #[default = false]
+    /// Record a trace of the game values
+    ///
+    /// Keeps the last room changes, splits and timer values in memory to attach to bug reports
+    pub trace_enable: bool,
+
+    #[default = false]
+    /// Write the recorded trace to the log
+    ///
+    /// Unticks itself once the trace is written
+    pub trace_dump: bool,
+}
+
+impl CampaignChoice {
+    /**
+     * The campaign picked, None to detect it
+     */
+    pub fn campaign(&self) -> Option<Campaign> {
+        match self {
+            CampaignChoice::Detect => None,
+            CampaignChoice::Peppino => Some(Campaign::Peppino),
+            CampaignChoice::Noise => Some(Campaign::Noise),
+            CampaignChoice::Swap => Some(Campaign::Swap),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CampaignChoice::Detect => "detect",
+            CampaignChoice::Peppino => "peppino",
+            CampaignChoice::Noise => "noise",
+            CampaignChoice::Swap => "swap",
+        }
+    }
+
+    #[cfg(any(test, feature = "replay"))]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "detect" => Some(CampaignChoice::Detect),
+            "peppino" => Some(CampaignChoice::Peppino),
+            "noise" => Some(CampaignChoice::Noise),
+            "swap" => Some(CampaignChoice::Swap),
+            _ => None,
+        }
+    }
+}
+
+impl TimerMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimerMode::FullGame => "full_game",
+            TimerMode::IL => "il",
+            TimerMode::NewGamePlus => "new_game_plus",
+            TimerMode::IW => "iw",
+        }
+    }
+
+    #[cfg(any(test, feature = "replay"))]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full_game" => Some(TimerMode::FullGame),
+            "il" => Some(TimerMode::IL),
+            "new_game_plus" => Some(TimerMode::NewGamePlus),
+            "iw" => Some(TimerMode::IW),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * The recommended start, split and reset options for each timer mode, by settings key
+ */
+fn default_settings_for_mode(timer_mode: TimerMode) -> [(&'static str, bool); 10] {
+    match timer_mode {
+        TimerMode::FullGame => [
+            ("start_new_file", true),
+            ("start_any_file", false),
+            ("start_new_il", false),
+            ("start_any_il", false),
+            ("start_exit_level", false),
+            ("splits_level_end", true),
+            ("splits_rooms", false),
+            ("reset_new_file", true),
+            ("reset_any_file", false),
+            ("reset_new_level", false),
+        ],
+        TimerMode::IL => [
+            ("start_new_file", false),
+            ("start_any_file", false),
+            ("start_new_il", true),
+            ("start_any_il", false),
+            ("start_exit_level", false),
+            ("splits_level_end", true),
+            ("splits_rooms", true),
+            ("reset_new_file", true),
+            ("reset_any_file", true),
+            ("reset_new_level", true),
+        ],
+        TimerMode::NewGamePlus => [
+            ("start_new_file", false),
+            ("start_any_file", true),
+            ("start_new_il", false),
+            ("start_any_il", false),
+            ("start_exit_level", false),
+            ("splits_level_end", true),
+            ("splits_rooms", false),
+            ("reset_new_file", true),
+            ("reset_any_file", true),
+            ("reset_new_level", false),
+        ],
+        TimerMode::IW => [
+            ("start_new_file", true),
+            ("start_any_file", false),
+            ("start_new_il", false),
+            ("start_any_il", false),
+            ("start_exit_level", true),
+            ("splits_level_end", true),
+            ("splits_rooms", false),
+            ("reset_new_file", true),
+            ("reset_any_file", true),
+            ("reset_new_level", false),
+        ],
+    }
 }
 
 impl Settings {
@@ -102,61 +321,150 @@ impl Settings {
 
         let settings_map = asr::settings::Map::load();
 
-        match self.timer_mode.current {
-            TimerMode::FullGame => {
-                settings_map.insert("start_new_file", true);
-                settings_map.insert("start_any_file", false);
-                settings_map.insert("start_new_il", false);
-                settings_map.insert("start_exit_level", false);
-
-                settings_map.insert("splits_level_end", true);
-                settings_map.insert("splits_rooms", false);
-
-                settings_map.insert("reset_new_file", true);
-                settings_map.insert("reset_any_file", false);
-                settings_map.insert("reset_new_level", false);
-            }
-            TimerMode::IL => {
-                settings_map.insert("start_new_file", false);
-                settings_map.insert("start_any_file", false);
-                settings_map.insert("start_new_il", true);
-                settings_map.insert("start_exit_level", false);
-
-                settings_map.insert("splits_level_end", true);
-                settings_map.insert("splits_rooms", true);
-
-                settings_map.insert("reset_new_file", true);
-                settings_map.insert("reset_any_file", true);
-                settings_map.insert("reset_new_level", true);
-            }
-            TimerMode::NewGamePlus => {
-                settings_map.insert("start_new_file", false);
-                settings_map.insert("start_any_file", true);
-                settings_map.insert("start_new_il", false);
-                settings_map.insert("start_exit_level", false);
-
-                settings_map.insert("splits_level_end", true);
-                settings_map.insert("splits_rooms", false);
-
-                settings_map.insert("reset_new_file", true);
-                settings_map.insert("reset_any_file", true);
-                settings_map.insert("reset_new_level", false);
-            }
-            TimerMode::IW => {
-                settings_map.insert("start_new_file", true);
-                settings_map.insert("start_any_file", false);
-                settings_map.insert("start_new_il", false);
-                settings_map.insert("start_exit_level", true);
-
-                settings_map.insert("splits_level_end", true);
-                settings_map.insert("splits_rooms", false);
-
-                settings_map.insert("reset_new_file", true);
-                settings_map.insert("reset_any_file", true);
-                settings_map.insert("reset_new_level", false);
-            }
+        for (key, value) in default_settings_for_mode(self.timer_mode.current) {
+            settings_map.insert(key, value);
         }
 
         settings_map.store();
     }
+
+    /**
+     * The start, split, reset and game time options by settings key, the ones the splitter logic looks at
+     */
+    pub fn flags(&self) -> [(&'static str, bool); 21] {
+        [
+            ("start_enable", self.start_enable),
+            ("start_new_file", self.start_new_file),
+            ("start_any_file", self.start_any_file),
+            ("start_new_il", self.start_new_il),
+            ("start_any_il", self.start_any_il),
+            ("start_exit_level", self.start_exit_level),
+            ("splits_enable", self.splits_enable),
+            ("splits_level_end", self.splits_level_end),
+            ("splits_route", self.splits_route),
+            ("splits_route_skips", self.splits_route_skips),
+            ("splits_escape", self.splits_escape),
+            ("splits_laps", self.splits_laps),
+            ("splits_checkpoints", self.splits_checkpoints),
+            ("splits_secrets", self.splits_secrets),
+            ("splits_custom", self.splits_custom),
+            ("splits_rooms", self.splits_rooms),
+            ("reset_enable", self.reset_enable),
+            ("reset_new_file", self.reset_new_file),
+            ("reset_any_file", self.reset_any_file),
+            ("reset_new_level", self.reset_new_level),
+            ("game_time_remove_loads", self.game_time_remove_loads),
+        ]
+    }
+
+    /**
+     * Set one of the options returned by flags, returns false if the key is not one of them
+     */
+    #[cfg(any(test, feature = "replay"))]
+    pub fn set_flag(&mut self, key: &str, value: bool) -> bool {
+        let flag = match key {
+            "start_enable" => &mut self.start_enable,
+            "start_new_file" => &mut self.start_new_file,
+            "start_any_file" => &mut self.start_any_file,
+            "start_new_il" => &mut self.start_new_il,
+            "start_any_il" => &mut self.start_any_il,
+            "start_exit_level" => &mut self.start_exit_level,
+            "splits_enable" => &mut self.splits_enable,
+            "splits_level_end" => &mut self.splits_level_end,
+            "splits_route" => &mut self.splits_route,
+            "splits_route_skips" => &mut self.splits_route_skips,
+            "splits_escape" => &mut self.splits_escape,
+            "splits_laps" => &mut self.splits_laps,
+            "splits_checkpoints" => &mut self.splits_checkpoints,
+            "splits_secrets" => &mut self.splits_secrets,
+            "splits_custom" => &mut self.splits_custom,
+            "splits_rooms" => &mut self.splits_rooms,
+            "reset_enable" => &mut self.reset_enable,
+            "reset_new_file" => &mut self.reset_new_file,
+            "reset_any_file" => &mut self.reset_any_file,
+            "reset_new_level" => &mut self.reset_new_level,
+            "game_time_remove_loads" => &mut self.game_time_remove_loads,
+            _ => return false,
+        };
+        *flag = value;
+        true
+    }
+
+    /**
+     * Untick an option in the GUI that does something once
+     */
+    fn uncheck(key: &str) {
+        let settings_map = asr::settings::Map::load();
+        settings_map.insert(key, false);
+        settings_map.store();
+    }
+
+    /**
+     * Untick the trace dump option in the GUI after the trace was written
+     */
+    pub fn uncheck_trace_dump(&mut self) {
+        Self::uncheck("trace_dump");
+        self.trace_dump = false;
+    }
+
+    /**
+     * Untick the practice reset option in the GUI after the times were forgotten
+     */
+    pub fn uncheck_practice_reset(&mut self) {
+        Self::uncheck("practice_reset");
+        self.practice_reset = false;
+    }
+
+    /**
+     * Settings as they would be after picking a mode in a fresh layout, without touching the runtime
+     */
+    #[cfg(any(test, feature = "replay"))]
+    pub fn for_mode(timer_mode: TimerMode) -> Self {
+        let mut settings = Self {
+            _message: Title,
+            _igt_mode: Title,
+            timer_mode: Pair {
+                old: timer_mode,
+                current: timer_mode,
+            },
+            timer_mode_load_defaults: true,
+            game_time_remove_loads: true,
+            campaign: CampaignChoice::Detect,
+            _timer_mode_title: Title,
+            start_enable: true,
+            start_new_file: true,
+            start_any_file: false,
+            start_new_il: false,
+            start_any_il: false,
+            start_exit_level: false,
+            _splits_title: Title,
+            splits_enable: true,
+            splits_level_end: true,
+            splits_route: false,
+            splits_route_skips: false,
+            splits_escape: false,
+            splits_laps: false,
+            splits_checkpoints: false,
+            splits_secrets: false,
+            splits_custom: false,
+            splits_rooms: false,
+            _reset_title: Title,
+            reset_enable: true,
+            reset_new_file: true,
+            reset_any_file: true,
+            reset_new_level: true,
+            _practice_title: Title,
+            practice_enable: false,
+            practice_reset: false,
+            _debug_title: Title,
+            trace_enable: false,
+            trace_dump: false,
+        };
+
+        for (key, value) in default_settings_for_mode(timer_mode) {
+            settings.set_flag(key, value);
+        }
+
+        settings
+    }
 }
@@ -0,0 +1,581 @@
+use asr::arrayvec::ArrayVec;
+use asr::string::ArrayCString;
+use asr::timer::TimerState;
+use asr::watcher::Pair;
+
+use crate::custom_splits::CustomSplits;
+use crate::memory::ROOM_NAME_SIZE_CAP;
+use crate::room_names::{self, Campaign, Level};
+use crate::route::Route;
+use crate::settings::{Settings, TimerMode};
+use crate::MemoryValues;
+
+/**
+ * Something the splitter wants LiveSplit to do, returned by a tick instead of being done right away
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimerAction {
+    Start,
+    Split,
+    /// skip the current split without a time, used when the route skipped a level
+    SkipSplit,
+    Reset,
+    /// game time in seconds, the game time should stay paused so it doesn't stutter
+    SetGameTime(f64),
+    /// stop and restart livesplit's own game time, for load removal when the game time can't be read
+    PauseGameTime,
+    ResumeGameTime,
+}
+
+pub type TimerActions = ArrayVec<TimerAction, 16>;
+
+// splits past this many in a run can't be undone cleanly, more than any category has
+const SPLIT_HISTORY_CAPACITY: usize = 256;
+
+/**
+ * What the split rules have already done in the run, kept together so it can be put back after an undo
+ */
+#[derive(Clone, Copy)]
+struct SplitProgress {
+    enable_full_game_split: bool,
+    ctop_oob_split: bool,    // should only happen once per run
+    escape_split_done: bool, // once per level visit
+    checkpoints_reached: u8, // bit per room in room_names::checkpoint_rooms, once per level visit
+    route_position: u8,      // levels of the route already split or skipped
+    // bit per secret of each level in room_names::collectible_levels, once per run
+    secrets_found: [u8; room_names::COLLECTIBLE_LEVEL_COUNT],
+
+    last_room_split_name: ArrayCString<ROOM_NAME_SIZE_CAP>,
+    last_room_split_time: f64,
+}
+
+impl SplitProgress {
+    fn new() -> Self {
+        Self {
+            enable_full_game_split: false,
+            ctop_oob_split: false,
+            escape_split_done: false,
+            checkpoints_reached: 0,
+            route_position: 0,
+            secrets_found: [0; room_names::COLLECTIBLE_LEVEL_COUNT],
+            last_room_split_name: ArrayCString::new(),
+            last_room_split_time: 0.0,
+        }
+    }
+}
+
+/**
+ * Everything the start, split, reset and game time rules need to remember between ticks
+ */
+pub struct SplitterState {
+    game_time_available: bool,
+    timer_state: TimerState,
+    // whether livesplit's game time was last told to pause for a load, None when it has to be told again
+    loads_paused: Option<bool>,
+
+    current_level: Level,
+    exited_level: Level, // the last level left, ignoring the hub and the results screen
+    current_lap: u8,     // 1 until a lap portal is taken, per level visit
+    campaign: Campaign,  // of the save being played, forgotten when a save is opened
+    il_level: Option<Level>, // the level an individual level run was started in
+    split_index: u64,    // livesplit's current split, counted here when livesplit can't be asked
+    igt_file_secs_calculated: Pair<f64>,
+    igt_level_secs_calculated: Pair<f64>,
+
+    ng_plus_offset_seconds: Option<f64>,
+    iw_offset_seconds: Option<f64>,
+
+    progress: SplitProgress,
+    // what progress was before each split of the run, to go back to when a split is undone
+    split_history: ArrayVec<SplitProgress, SPLIT_HISTORY_CAPACITY>,
+    progress_at_tick_start: SplitProgress,
+
+    custom_splits: CustomSplits,
+    route: Route,
+}
+
+impl SplitterState {
+    /**
+     * game_time_available should be true when the buffer helper was found, otherwise livesplit's game time only skips loads
+     */
+    pub fn new(game_time_available: bool) -> Self {
+        Self {
+            game_time_available,
+            timer_state: TimerState::NotRunning,
+            loads_paused: None,
+            current_level: Level::Unknown,
+            exited_level: Level::Unknown,
+            current_lap: 1,
+            campaign: Campaign::Unknown,
+            il_level: None,
+            split_index: 0,
+            igt_file_secs_calculated: Pair::default(),
+            igt_level_secs_calculated: Pair::default(),
+            ng_plus_offset_seconds: None,
+            iw_offset_seconds: None,
+            progress: SplitProgress::new(),
+            split_history: ArrayVec::new(),
+            progress_at_tick_start: SplitProgress::new(),
+            custom_splits: CustomSplits::default(),
+            route: Route::default(),
+        }
+    }
+
+    pub fn game_time_available(&self) -> bool {
+        self.game_time_available
+    }
+
+    pub fn current_level(&self) -> Level {
+        self.current_level
+    }
+
+    pub fn current_lap(&self) -> u8 {
+        self.current_lap
+    }
+
+    pub fn campaign(&self) -> Campaign {
+        self.campaign
+    }
+
+    pub fn current_il(&self) -> Option<Level> {
+        self.il_level
+    }
+
+    /**
+     * The secrets found in the current level and in the whole run since the timer started
+     */
+    pub fn secret_counts(&self) -> (Option<u32>, u32) {
+        let level = room_names::collectible_levels()
+            .iter()
+            .position(|level| *level == self.current_level)
+            .map(|index| self.progress.secrets_found[index].count_ones());
+        let run = self
+            .progress
+            .secrets_found
+            .iter()
+            .map(|found| found.count_ones())
+            .sum();
+        (level, run)
+    }
+
+    pub fn custom_splits(&self) -> &CustomSplits {
+        &self.custom_splits
+    }
+
+    pub fn set_custom_splits(&mut self, custom_splits: CustomSplits) {
+        self.custom_splits = custom_splits;
+    }
+
+    pub fn route(&self) -> &Route {
+        &self.route
+    }
+
+    pub fn set_route(&mut self, route: Route) {
+        self.route = route;
+    }
+
+    /**
+     * Use livesplit's current split index, so the route and the split rules stay right after manual splits, skips and undos.
+     * Undoing puts back the progress from before the undone splits so they can happen again,
+     * skipping counts the level being played as split so leaving it later doesn't split again.
+     * With route on, every split done by hand also passes a level of the route
+     */
+    pub fn sync_split_index(&mut self, split_index: Option<u64>, route_enabled: bool) {
+        let Some(index) = split_index else {
+            return;
+        };
+        if index < self.split_index {
+            while self.split_history.len() > index as usize {
+                if let Some(progress) = self.split_history.pop() {
+                    self.progress = progress;
+                }
+            }
+        } else if index > self.split_index {
+            for _ in self.split_index..index {
+                let _ = self.split_history.try_push(self.progress);
+                if route_enabled {
+                    self.progress.route_position = self.progress.route_position.saturating_add(1);
+                }
+            }
+            self.progress.enable_full_game_split = false;
+        }
+        self.split_index = index;
+    }
+
+    /**
+     * forget the splits and run counters of the last run, when the timer is reset by the splitter or by hand
+     */
+    fn forget_run(&mut self) {
+        self.il_level = None;
+        self.split_index = 0;
+        self.split_history.clear();
+        self.progress.secrets_found = [0; room_names::COLLECTIBLE_LEVEL_COUNT];
+        self.progress.route_position = 0;
+    }
+
+    /**
+     * keeps track of what the timer will look like after the actions of this tick are done
+     */
+    fn push(&mut self, actions: &mut TimerActions, action: TimerAction) {
+        match action {
+            TimerAction::Start => {
+                if self.timer_state == TimerState::NotRunning {
+                    self.timer_state = TimerState::Running;
+                    self.loads_paused = None;
+                    self.forget_run();
+                }
+            }
+            TimerAction::Split | TimerAction::SkipSplit => {
+                if self.timer_state == TimerState::Running {
+                    self.split_index += 1;
+                    // progress from before this tick's rules ran, the split's own changes are what an undo takes back
+                    let _ = self.split_history.try_push(self.progress_at_tick_start);
+                }
+            }
+            TimerAction::Reset => {
+                self.timer_state = TimerState::NotRunning;
+                self.forget_run();
+            }
+            TimerAction::PauseGameTime => self.loads_paused = Some(true),
+            TimerAction::ResumeGameTime => self.loads_paused = Some(false),
+            TimerAction::SetGameTime(_) => {}
+        }
+        let _ = actions.try_push(action);
+    }
+
+    /**
+     * split only if the level just left is the next one of the route, or the one after it when skips are allowed.
+     * The route keeps its own position, the other kinds of splits don't move it
+     */
+    fn push_route_split(&mut self, actions: &mut TimerActions, allow_skips: bool) {
+        let position = self.progress.route_position as usize;
+        if self.route.get(position) == Some(&self.exited_level) {
+            self.push(actions, TimerAction::Split);
+            self.progress.route_position += 1;
+        } else if allow_skips && self.route.get(position + 1) == Some(&self.exited_level) {
+            self.push(actions, TimerAction::SkipSplit);
+            self.push(actions, TimerAction::Split);
+            self.progress.route_position += 2;
+        }
+    }
+
+    /**
+     * run every rule for one tick of memory values and return what the timer should do, in order
+     */
+    pub fn tick(
+        &mut self,
+        mem_values: &MemoryValues,
+        settings: &Settings,
+        timer_state: TimerState,
+    ) -> TimerActions {
+        let mut actions = TimerActions::new();
+        self.timer_state = timer_state;
+        // a reset by hand, livesplit's split index of the next run starts over without going through an undo
+        if timer_state == TimerState::NotRunning {
+            self.forget_run();
+        }
+        self.progress_at_tick_start = self.progress;
+
+        let room_name_parsed_current = mem_values
+            .room_name
+            .current
+            .validate_utf8()
+            .unwrap_or("(invalid utf8 string)");
+        let room_name_parsed_old = mem_values
+            .room_name
+            .old
+            .validate_utf8()
+            .unwrap_or("(invalid utf8 string)");
+
+        // update current level and enable full game splits
+        let mut escape_started = false;
+        let mut checkpoint_reached = false;
+        let mut lap_started = false;
+        let mut secret_found = false;
+        let mut level_entered = false;
+        if mem_values.room_name.changed() {
+            let new_level =
+                room_names::get_current_level(room_name_parsed_current, self.current_level);
+            if new_level != self.current_level {
+                level_entered = true;
+                if self.current_level != Level::Hub && self.current_level != Level::ResultsScreen {
+                    self.exited_level = self.current_level;
+                }
+                self.progress.escape_split_done = false;
+                self.progress.checkpoints_reached = 0;
+                self.current_lap = 1;
+            }
+            self.current_level = new_level;
+            if room_name_parsed_current == "Finalintro"
+                || room_name_parsed_current == "hub_loadingscreen"
+            {
+                self.campaign = Campaign::Unknown;
+            }
+            if let Some(campaign) = room_names::campaign_of_level(&self.current_level) {
+                self.campaign = campaign;
+            }
+            if !self.progress.enable_full_game_split {
+                self.progress.enable_full_game_split =
+                    room_names::full_game_split_unlock_rooms(room_name_parsed_current);
+            }
+            // the lap portal is in the first room of the level and brings you back to the escape start
+            if self.progress.escape_split_done
+                && room_names::escape_start_rooms(room_name_parsed_current)
+                && room_name_parsed_old == room_names::get_starting_room(&self.current_level)
+            {
+                self.current_lap = self.current_lap.saturating_add(1);
+                lap_started = true;
+            }
+            if !self.progress.escape_split_done
+                && room_names::escape_start_rooms(room_name_parsed_current)
+            {
+                self.progress.escape_split_done = true;
+                escape_started = true;
+            }
+            if let (Some(level_index), Some(secret)) = (
+                room_names::collectible_levels()
+                    .iter()
+                    .position(|level| *level == self.current_level),
+                room_names::secret_index(room_name_parsed_current),
+            ) {
+                let found = &mut self.progress.secrets_found[level_index];
+                if *found & (1 << secret) == 0 {
+                    *found |= 1 << secret;
+                    secret_found = true;
+                }
+            }
+            if let Some(index) = room_names::checkpoint_rooms(&self.current_level)
+                .iter()
+                .position(|&room| room == room_name_parsed_current)
+            {
+                if self.progress.checkpoints_reached & (1 << index) == 0 {
+                    self.progress.checkpoints_reached |= 1 << index;
+                    checkpoint_reached = true;
+                }
+            }
+        }
+
+        // the campaign picked in the settings, swap mode and a run without the tutorial can't be told apart otherwise
+        if let Some(campaign) = settings.campaign.campaign() {
+            self.campaign = campaign;
+        }
+
+        // game time set
+        if self.game_time_available {
+            self.igt_file_secs_calculated.old = self.igt_file_secs_calculated.current;
+            self.igt_file_secs_calculated.current =
+                mem_values.file_minutes.current * 60.0 + mem_values.file_seconds.current;
+            self.igt_level_secs_calculated.old = self.igt_level_secs_calculated.current;
+            self.igt_level_secs_calculated.current =
+                mem_values.level_minutes.current * 60.0 + mem_values.level_seconds.current;
+
+            // offsets for ng+ and iw
+            if self.timer_state == TimerState::NotRunning {
+                // ng+ offset update
+                if self.ng_plus_offset_seconds.is_none()
+                    && room_name_parsed_current == "tower_entrancehall"
+                    && mem_values.level_minutes.current == 0.0
+                    && mem_values.level_seconds.current < 1.0
+                {
+                    self.ng_plus_offset_seconds = Some(self.igt_file_secs_calculated.current);
+                }
+                if self.ng_plus_offset_seconds.is_some()
+                    && (room_name_parsed_current == "hub_loadingscreen"
+                        || room_name_parsed_current == "Finalintro")
+                {
+                    self.ng_plus_offset_seconds = None;
+                }
+
+                // iw offset update
+                if self.iw_offset_seconds.is_none() && self.current_level == Level::Hub {
+                    self.iw_offset_seconds = Some(self.igt_file_secs_calculated.current);
+                }
+                if self.iw_offset_seconds.is_some() && self.current_level != Level::Hub {
+                    self.iw_offset_seconds = None;
+                }
+            }
+
+            let game_time_livesplit = match settings.timer_mode.current {
+                TimerMode::FullGame => self.igt_file_secs_calculated.current,
+                TimerMode::IL => self.igt_level_secs_calculated.current,
+                TimerMode::NewGamePlus => {
+                    self.igt_file_secs_calculated.current
+                        - self.ng_plus_offset_seconds.unwrap_or(0.0)
+                }
+                TimerMode::IW => {
+                    self.igt_file_secs_calculated.current - self.iw_offset_seconds.unwrap_or(0.0)
+                }
+            };
+            self.push(&mut actions, TimerAction::SetGameTime(game_time_livesplit));
+        } else if settings.game_time_remove_loads && self.timer_state == TimerState::Running {
+            // without the buffer helper livesplit keeps the game time, running with real time outside of loads
+            let loading = room_names::loading_rooms(room_name_parsed_current);
+            if self.loads_paused != Some(loading) {
+                let action = if loading {
+                    TimerAction::PauseGameTime
+                } else {
+                    TimerAction::ResumeGameTime
+                };
+                self.push(&mut actions, action);
+            }
+        }
+
+        // reset
+        if settings.reset_enable {
+            if settings.reset_new_file
+                && room_name_parsed_current == "Finalintro"
+                && room_name_parsed_old != "Finalintro"
+            {
+                self.push(&mut actions, TimerAction::Reset);
+            }
+            if settings.reset_any_file
+                && mem_values.room_name.changed()
+                && room_name_parsed_current == "hub_loadingscreen"
+            {
+                self.push(&mut actions, TimerAction::Reset);
+            }
+            if settings.reset_new_level
+                && self.igt_level_secs_calculated.decreased()
+                && self.current_level != Level::Hub
+            {
+                self.progress.last_room_split_time = 0.0;
+                self.push(&mut actions, TimerAction::Reset);
+            }
+        }
+
+        // practicing any level, entering one starts over
+        if settings.start_enable
+            && settings.start_any_il
+            && level_entered
+            && room_names::get_starting_room(&self.current_level) == room_name_parsed_current
+            && self.timer_state != TimerState::NotRunning
+        {
+            self.push(&mut actions, TimerAction::Reset);
+        }
+
+        // split
+        if settings.splits_enable {
+            // covers any full game split
+            if settings.splits_level_end {
+                // standard level / boss end, a save not known to be peppino's might be the noise's
+                let boss_defeated = mem_values.boss_hp.old == 0
+                    || (self.campaign != Campaign::Peppino
+                        && room_names::noise_final_boss_exit(
+                            room_name_parsed_old,
+                            room_name_parsed_current,
+                        ));
+                if mem_values.room_name.changed()
+                    && room_names::full_game_split_rooms(room_name_parsed_old)
+                    && (self.current_level == Level::Hub
+                        || self.current_level == Level::ResultsScreen)
+                    && self.progress.enable_full_game_split
+                    && boss_defeated
+                {
+                    if settings.splits_route {
+                        self.push_route_split(&mut actions, settings.splits_route_skips);
+                    } else {
+                        self.push(&mut actions, TimerAction::Split);
+                    }
+                    self.progress.enable_full_game_split = false;
+                }
+
+                // end of the run frame perfect split, technically the prev "if" could cover this too but frame perfectly splitting at the end is cooler
+                if mem_values.end_of_level.current == 1
+                    && mem_values.end_of_level.old == 0
+                    && room_name_parsed_current == "tower_entrancehall"
+                {
+                    self.push(&mut actions, TimerAction::Split);
+                }
+
+                // ctop entering from oob
+                if self.timer_state == TimerState::NotRunning && self.progress.ctop_oob_split {
+                    self.progress.ctop_oob_split = false;
+                }
+                if room_name_parsed_current == "tower_finalhallway"
+                    && room_name_parsed_old == "tower_5"
+                    && !self.progress.ctop_oob_split
+                {
+                    self.progress.ctop_oob_split = true;
+                    self.push(&mut actions, TimerAction::Split);
+                }
+            }
+
+            // the escape start and the secrets are also checkpoints, only split once if several are on
+            if (settings.splits_escape && escape_started)
+                || (settings.splits_checkpoints && checkpoint_reached)
+                || (settings.splits_secrets && secret_found)
+            {
+                self.push(&mut actions, TimerAction::Split);
+            }
+
+            if settings.splits_laps && lap_started {
+                self.push(&mut actions, TimerAction::Split);
+            }
+
+            if settings.splits_custom
+                && mem_values.room_name.changed()
+                && self
+                    .custom_splits
+                    .matches(room_name_parsed_old, room_name_parsed_current)
+            {
+                self.push(&mut actions, TimerAction::Split);
+            }
+
+            let last_room_transition_time =
+                self.igt_file_secs_calculated.current - self.progress.last_room_split_time;
+            if settings.splits_rooms
+                && (!(0.0..=2.0).contains(&last_room_transition_time)
+                    || mem_values.room_name.current != self.progress.last_room_split_name)
+                && mem_values.room_name.changed()
+                && room_name_parsed_old != "rank_room"
+            {
+                self.progress.last_room_split_time = self.igt_file_secs_calculated.current;
+                self.progress.last_room_split_name = mem_values.room_name.old;
+
+                self.push(&mut actions, TimerAction::Split);
+            }
+        }
+
+        // start
+        if settings.start_enable {
+            if settings.start_new_file
+                && room_name_parsed_current == "tower_entrancehall"
+                && room_name_parsed_old == "Finalintro"
+            {
+                self.push(&mut actions, TimerAction::Start);
+            }
+            if settings.start_any_file
+                && room_name_parsed_current == "tower_entrancehall"
+                && room_name_parsed_old == "hub_loadingscreen"
+            {
+                self.push(&mut actions, TimerAction::Start);
+            }
+            let in_starting_room =
+                room_names::get_starting_room(&self.current_level) == room_name_parsed_current;
+            // without game time the level timer can't be seen starting, entering the level has to do
+            let il_started = if self.game_time_available {
+                self.igt_level_secs_calculated.current > 0.07
+                    && self.igt_level_secs_calculated.current <= 0.1
+            } else {
+                settings.start_any_il && level_entered
+            };
+            if (settings.start_new_il || settings.start_any_il) && in_starting_room && il_started {
+                // livesplit ignores a start while the timer runs, the run keeps the level it was started in
+                let starts_run = self.timer_state == TimerState::NotRunning;
+                self.push(&mut actions, TimerAction::Start);
+                if starts_run {
+                    self.il_level = Some(self.current_level);
+                }
+            }
+            if settings.start_exit_level
+                && mem_values.room_name.changed()
+                && room_names::full_game_split_rooms(room_name_parsed_old)
+                && self.current_level == Level::Hub
+            {
+                self.push(&mut actions, TimerAction::Start);
+            }
+        }
+
+        actions
+    }
+}
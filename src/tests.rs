@@ -0,0 +1,1375 @@
+//! Host side harness that plays scripted game frames through the memory reader and the splitter.
+//! Run with `cargo test --target x86_64-unknown-linux-gnu` (or your host target).
+
+use core::mem::offset_of;
+use std::vec;
+use std::vec::Vec;
+
+use asr::timer::TimerState;
+use asr::Address;
+
+use crate::buffer_helper::{BufferHeader, BufferLayout, BufferV1};
+use crate::custom_splits::{CustomSplit, CustomSplits};
+use crate::fake_process::{FakeProcess, FakeRange};
+use crate::memory::{
+    format_time, read_room_id, refresh_mem_values, room_id_sigscan_start, CountingSource,
+};
+use crate::practice::{BestTimes, Practice};
+use crate::replay::{replay, room_cstring, TimerSim};
+use crate::room_catalogue::{is_known_room, UnknownRooms};
+use crate::room_names::{
+    checkpoint_rooms, collectible_levels, full_game_route, get_current_level, get_full_level_name,
+    get_starting_room, level_room_tables, Campaign, Level,
+};
+use crate::route::Route;
+use crate::settings::{CampaignChoice, Settings, TimerMode};
+use crate::splitter::{SplitterState, TimerAction};
+use crate::trace::{TraceRecorder, TRACE_HEADER};
+use crate::{MemoryAddresses, MemoryValues};
+
+const MAIN_ADDRESS: u64 = 0x1000_0000;
+const ROOM_ID_OFFSET: u64 = 0x100;
+const BUFFER_HELPER_ADDRESS: u64 = 0x2000_0000;
+
+/**
+ * What the buffer helper reports on a single tick
+ */
+#[derive(Clone, Copy)]
+struct Frame {
+    room: &'static str,
+    file_secs: f64,
+    level_secs: f64,
+    end_of_level: u8,
+    boss_hp: u8,
+}
+
+fn frame(room: &'static str, file_secs: f64, level_secs: f64) -> Frame {
+    Frame {
+        room,
+        file_secs,
+        level_secs,
+        end_of_level: 0,
+        boss_hp: 0,
+    }
+}
+
+/**
+ * Fake game memory laid out like the -livesplit buffer helper
+ */
+struct FakeGame {
+    main_module: [u8; 0x200],
+    buffer_helper: [u8; 0x100],
+}
+
+impl FakeGame {
+    fn new() -> Self {
+        let mut game = Self {
+            main_module: [0; 0x200],
+            buffer_helper: [0; 0x100],
+        };
+        game.write_str(0x40, "v1.1.063");
+        game
+    }
+
+    fn write_str(&mut self, offset: usize, text: &str) {
+        let field = &mut self.buffer_helper[offset..offset + 0x40];
+        field.fill(0);
+        field[..text.len()].copy_from_slice(text.as_bytes());
+    }
+
+    fn write_f64(&mut self, offset: usize, value: f64) {
+        self.buffer_helper[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_frame(&mut self, room_id: i32, frame: &Frame) {
+        let room_id_offset = ROOM_ID_OFFSET as usize;
+        self.main_module[room_id_offset..room_id_offset + 4]
+            .copy_from_slice(&room_id.to_le_bytes());
+
+        self.write_f64(0x80, (frame.file_secs / 60.0).floor());
+        self.write_f64(0x88, frame.file_secs % 60.0);
+        self.write_f64(0x90, (frame.level_secs / 60.0).floor());
+        self.write_f64(0x98, frame.level_secs % 60.0);
+        self.write_str(0xA0, frame.room);
+        self.buffer_helper[0xE0] = frame.end_of_level;
+        self.buffer_helper[0xE1] = frame.boss_hp;
+    }
+
+    fn ranges(&self) -> [FakeRange<'_>; 2] {
+        [
+            FakeRange {
+                address: Address::new(MAIN_ADDRESS),
+                bytes: &self.main_module,
+            },
+            FakeRange {
+                address: Address::new(BUFFER_HELPER_ADDRESS),
+                bytes: &self.buffer_helper,
+            },
+        ]
+    }
+}
+
+fn fake_addresses() -> MemoryAddresses {
+    MemoryAddresses {
+        main_address: Some(Address::new(MAIN_ADDRESS)),
+        room_id: Some(Address::new(ROOM_ID_OFFSET)),
+        room_names: None,
+        buffer_helper: Some(Address::new(BUFFER_HELPER_ADDRESS)),
+    }
+}
+
+/**
+ * Feeds every frame through refresh_mem_values and the splitter, pretending to be LiveSplit.
+ * Returns the actions livesplit would have taken with the index of the frame that caused them, and the last game time.
+ */
+fn run(settings: &Settings, frames: &[Frame]) -> (Vec<(usize, TimerAction)>, f64) {
+    run_with(
+        SplitterState::new(true),
+        settings,
+        frames,
+        RunHooks::default(),
+    )
+}
+
+// looks at the splitter and the values read after the tick of the frame with the given index
+type AfterTick<'a> = &'a mut dyn FnMut(usize, &SplitterState, &MemoryValues);
+
+/**
+ * What run_with does on each frame besides reading it and ticking the splitter, nothing by default
+ */
+#[derive(Default)]
+struct RunHooks<'a> {
+    // the split index livesplit reports before each frame, to fake manual undos and skips
+    split_index: Option<&'a dyn Fn(usize) -> Option<u64>>,
+    // the timer state before each frame when the runner changed it by hand
+    timer_state: Option<&'a dyn Fn(usize) -> Option<TimerState>>,
+    after_tick: Option<AfterTick<'a>>,
+}
+
+/**
+ * Like run, with a prepared splitter and the hooks
+ */
+fn run_with(
+    mut splitter: SplitterState,
+    settings: &Settings,
+    frames: &[Frame],
+    mut hooks: RunHooks,
+) -> (Vec<(usize, TimerAction)>, f64) {
+    let mut game = FakeGame::new();
+    let addresses = fake_addresses();
+    let mut values = MemoryValues::default();
+    let mut timer = TimerSim::new();
+    let mut game_time = 0.0;
+    let mut events = Vec::new();
+
+    for (index, frame) in frames.iter().enumerate() {
+        game.write_frame(index as i32 + 1, frame);
+        let ranges = game.ranges();
+        let process = FakeProcess { ranges: &ranges };
+        refresh_mem_values(&process, &addresses, &mut values).unwrap();
+        if let Some(state) = hooks.timer_state.and_then(|timer_state| timer_state(index)) {
+            timer.state = state;
+        }
+        if let Some(split_index) = hooks.split_index {
+            splitter.sync_split_index(split_index(index), settings.splits_route);
+        }
+
+        for action in splitter.tick(&values, settings, timer.state) {
+            if !timer.apply(action) {
+                continue;
+            }
+            match action {
+                TimerAction::SetGameTime(seconds) => game_time = seconds,
+                _ => events.push((index, action)),
+            }
+        }
+        if let Some(after_tick) = &mut hooks.after_tick {
+            after_tick(index, &splitter, &values);
+        }
+    }
+
+    (events, game_time)
+}
+
+#[test]
+fn refresh_reads_the_buffer_helper() {
+    let mut game = FakeGame::new();
+    let mut values = MemoryValues::default();
+    game.write_frame(
+        42,
+        &Frame {
+            room: "entrance_1",
+            file_secs: 125.5,
+            level_secs: 3.25,
+            end_of_level: 1,
+            boss_hp: 7,
+        },
+    );
+
+    let ranges = game.ranges();
+    let process = FakeProcess { ranges: &ranges };
+    refresh_mem_values(&process, &fake_addresses(), &mut values).unwrap();
+
+    assert_eq!(values.room_id.current, 42);
+    assert!(values.room_name.current.matches("entrance_1"));
+    assert!(values.game_version.current.matches("v1.1.063"));
+    assert_eq!(values.file_minutes.current, 2.0);
+    assert_eq!(values.file_seconds.current, 5.5);
+    assert_eq!(values.level_seconds.current, 3.25);
+    assert_eq!(values.end_of_level.current, 1);
+    assert_eq!(values.boss_hp.current, 7);
+}
+
+#[test]
+fn buffer_layout_matches_the_documented_offsets() {
+    assert_eq!(offset_of!(BufferHeader, game_version), 0x40);
+    assert_eq!(offset_of!(BufferV1, file_minutes), 0x80);
+    assert_eq!(offset_of!(BufferV1, file_seconds), 0x88);
+    assert_eq!(offset_of!(BufferV1, level_minutes), 0x90);
+    assert_eq!(offset_of!(BufferV1, level_seconds), 0x98);
+    assert_eq!(offset_of!(BufferV1, room_name), 0xA0);
+    assert_eq!(offset_of!(BufferV1, end_of_level), 0xE0);
+    assert_eq!(offset_of!(BufferV1, boss_hp), 0xE1);
+
+    assert_eq!(
+        BufferLayout::for_version("v1.0.5952"),
+        Some(BufferLayout::V1)
+    );
+    assert_eq!(
+        BufferLayout::for_version("v1.1.063"),
+        Some(BufferLayout::V1)
+    );
+    assert_eq!(BufferLayout::for_version("v2.0.0"), None);
+}
+
+#[test]
+fn refresh_reads_unknown_versions_with_the_latest_layout() {
+    let mut game = FakeGame::new();
+    let mut values = MemoryValues::default();
+    game.write_str(0x40, "v2.0.0");
+    game.write_frame(1, &frame("medieval_3", 30.0, 12.0));
+
+    let ranges = game.ranges();
+    let process = FakeProcess { ranges: &ranges };
+    refresh_mem_values(&process, &fake_addresses(), &mut values).unwrap();
+
+    assert!(values.game_version.current.matches("v2.0.0"));
+    assert!(values.room_name.current.matches("medieval_3"));
+    assert_eq!(values.level_seconds.current, 12.0);
+}
+
+#[test]
+fn refresh_reads_the_buffer_in_one_go_or_field_by_field() {
+    let mut game = FakeGame::new();
+    let mut values = MemoryValues::default();
+    game.write_frame(1, &frame("ruin_2", 20.0, 8.0));
+    {
+        let ranges = game.ranges();
+        let process = FakeProcess { ranges: &ranges };
+        refresh_mem_values(&process, &fake_addresses(), &mut values).unwrap();
+
+        // once the version is known: the room id and the whole buffer
+        let counted_process = CountingSource::new(&process);
+        refresh_mem_values(&counted_process, &fake_addresses(), &mut values).unwrap();
+        assert_eq!(counted_process.reads(), 2);
+    }
+
+    game.write_frame(2, &frame("ruin_3", 21.0, 9.0));
+    let mut ranges = game.ranges();
+    // the end of the buffer is unreadable, so only the single fields can be read
+    ranges[1].bytes = &game.buffer_helper[..0xE2];
+    let process = FakeProcess { ranges: &ranges };
+    let counted_process = CountingSource::new(&process);
+    refresh_mem_values(&counted_process, &fake_addresses(), &mut values).unwrap();
+
+    assert_eq!(counted_process.reads(), 9);
+    assert!(values.room_name.current.matches("ruin_3"));
+    assert!(values.room_name.old.matches("ruin_2"));
+    assert_eq!(values.level_seconds.current, 9.0);
+}
+
+#[test]
+fn room_id_scan_finds_the_room_id() {
+    let mut game = FakeGame::new();
+    // mov [rip + offset], edi; cmp rbx, ... with the offset pointing at the room id
+    let instruction = [0x89, 0x3D, 0, 0, 0, 0, 0x48, 0x3B, 0x1D];
+    game.main_module[0x20..0x29].copy_from_slice(&instruction);
+    let offset = ROOM_ID_OFFSET as u32 - 0x26;
+    game.main_module[0x22..0x26].copy_from_slice(&offset.to_le_bytes());
+    game.write_frame(7, &frame("tower_1", 0.0, 0.0));
+    let ranges = game.ranges();
+    let process = FakeProcess { ranges: &ranges };
+    let mut addresses = fake_addresses();
+    addresses.room_id = None;
+
+    let room_id = room_id_sigscan_start(&process, &addresses).unwrap();
+    addresses.room_id = Some(room_id);
+
+    assert_eq!(room_id, Address::new(ROOM_ID_OFFSET));
+    assert_eq!(read_room_id(&process, &addresses), Ok(7));
+}
+
+#[test]
+fn refresh_fails_without_room_id() {
+    let process = FakeProcess { ranges: &[] };
+    let mut values = MemoryValues::default();
+
+    assert!(refresh_mem_values(&process, &fake_addresses(), &mut values).is_err());
+}
+
+#[test]
+fn full_game_start_split_and_end() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("entrance_1", 10.0, 0.0),
+        frame("entrance_10", 50.0, 40.0),
+        frame("entrance_1", 70.0, 60.0),
+        frame("tower_1", 71.0, 0.0),
+        frame("tower_entrancehall", 80.0, 0.0),
+        Frame {
+            end_of_level: 1,
+            ..frame("tower_entrancehall", 81.0, 0.0)
+        },
+    ];
+
+    let (events, game_time) = run(&settings, &frames);
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (5, TimerAction::Split),
+            (7, TimerAction::Split)
+        ]
+    );
+    assert_eq!(game_time, 81.0);
+}
+
+#[test]
+fn full_game_level_exit_needs_the_unlock_room() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("entrance_1", 10.0, 0.0),
+        frame("tower_1", 12.0, 0.0),
+    ];
+
+    let (events, _) = run(&settings, &frames);
+
+    assert_eq!(events, [(1, TimerAction::Start)]);
+}
+
+#[test]
+fn full_game_boss_split_waits_for_the_boss_to_die() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        Frame {
+            boss_hp: 3,
+            ..frame("boss_pepperman", 10.0, 0.0)
+        },
+        frame("tower_1", 20.0, 0.0),
+        frame("boss_pepperman", 30.0, 0.0),
+        frame("tower_1", 40.0, 0.0),
+    ];
+
+    let (events, _) = run(&settings, &frames);
+
+    assert_eq!(events, [(1, TimerAction::Start), (5, TimerAction::Split)]);
+}
+
+#[test]
+fn noise_final_boss_splits_only_in_the_noise_campaign() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let final_boss = |tutorial| {
+        [
+            frame("Finalintro", 0.0, 0.0),
+            frame("tower_entrancehall", 0.1, 0.1),
+            frame(tutorial, 5.0, 0.0),
+            frame("tower_entrancehall", 8.0, 0.0),
+            Frame {
+                boss_hp: 5,
+                ..frame("boss_pizzaface", 10.0, 0.0)
+            },
+            Frame {
+                boss_hp: 5,
+                ..frame("boss_pizzafacehub", 20.0, 0.0)
+            },
+        ]
+    };
+    let run_campaign = |settings: &Settings, frames: &[Frame]| {
+        let mut campaign = Campaign::Unknown;
+        let mut after_tick = |_, splitter: &SplitterState, _: &MemoryValues| {
+            campaign = splitter.campaign();
+        };
+        let hooks = RunHooks {
+            after_tick: Some(&mut after_tick),
+            ..Default::default()
+        };
+        let (events, _) = run_with(SplitterState::new(true), settings, frames, hooks);
+        (events, campaign)
+    };
+
+    let (peppino_events, peppino_campaign) =
+        run_campaign(&settings, &final_boss("tower_tutorial1"));
+    let (noise_events, noise_campaign) = run_campaign(&settings, &final_boss("tower_tutorial1N"));
+    // without the tutorial only the character picked in the settings tells
+    let (_, unknown_campaign) = run_campaign(&settings, &final_boss("tower_1"));
+    let mut picked = Settings::for_mode(TimerMode::FullGame);
+    picked.campaign = CampaignChoice::Peppino;
+    let (picked_peppino_events, picked_peppino) = run_campaign(&picked, &final_boss("tower_1"));
+    picked.campaign = CampaignChoice::Noise;
+    let (picked_noise_events, picked_noise) = run_campaign(&picked, &final_boss("tower_1"));
+    // swap mode plays both characters, the noise's final boss might be fought
+    picked.campaign = CampaignChoice::Swap;
+    let (picked_swap_events, picked_swap) = run_campaign(&picked, &final_boss("tower_1"));
+
+    assert_eq!(peppino_campaign, Campaign::Peppino);
+    assert_eq!(noise_campaign, Campaign::Noise);
+    assert_eq!(unknown_campaign, Campaign::Unknown);
+    assert_eq!(picked_peppino, Campaign::Peppino);
+    assert_eq!(picked_noise, Campaign::Noise);
+    assert_eq!(peppino_events, [(1, TimerAction::Start)]);
+    assert_eq!(picked_peppino_events, peppino_events);
+    assert_eq!(
+        noise_events,
+        [(1, TimerAction::Start), (5, TimerAction::Split)]
+    );
+    assert_eq!(picked_noise_events, noise_events);
+    assert_eq!(picked_swap, Campaign::Swap);
+    assert_eq!(picked_swap_events, noise_events);
+    assert_eq!(
+        get_full_level_name(&Level::Noise, Campaign::Noise),
+        "The Doise"
+    );
+}
+
+#[test]
+fn ctop_out_of_bounds_splits_once() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("tower_5", 10.0, 0.0),
+        frame("tower_finalhallway", 11.0, 0.0),
+        frame("tower_5", 12.0, 0.0),
+        frame("tower_finalhallway", 13.0, 0.0),
+    ];
+
+    let (events, _) = run(&settings, &frames);
+
+    assert_eq!(events, [(1, TimerAction::Start), (3, TimerAction::Split)]);
+}
+
+#[test]
+fn il_start_room_splits_and_restart() {
+    let settings = Settings::for_mode(TimerMode::IL);
+    let frames = [
+        frame("tower_1", 5.0, 0.0),
+        frame("medieval_1", 6.0, 0.0),
+        frame("medieval_1", 6.08, 0.08),
+        frame("medieval_2", 10.0, 4.0),
+        frame("medieval_3", 15.0, 9.0),
+        frame("medieval_1", 16.0, 0.0),
+        frame("medieval_1", 16.08, 0.08),
+    ];
+
+    let (events, game_time) = run(&settings, &frames);
+
+    assert_eq!(
+        events,
+        [
+            (2, TimerAction::Start),
+            (3, TimerAction::Split),
+            (4, TimerAction::Split),
+            (5, TimerAction::Reset),
+            (6, TimerAction::Start),
+        ]
+    );
+    assert!((game_time - 0.08).abs() < 1e-9);
+}
+
+#[test]
+fn il_practice_restarts_in_any_level() {
+    let mut settings = Settings::for_mode(TimerMode::IL);
+    settings.start_new_il = false;
+    settings.start_any_il = true;
+    settings.reset_new_level = false;
+    settings.splits_rooms = false;
+    let frames = [
+        frame("tower_1", 5.0, 0.0),
+        frame("medieval_1", 6.0, 0.0),
+        frame("medieval_1", 6.08, 0.08),
+        frame("medieval_2", 10.0, 4.0),
+        frame("tower_1", 15.0, 0.0),
+        frame("ruin_1", 16.0, 0.0),
+        frame("ruin_1", 16.08, 0.08),
+    ];
+
+    let mut current_il = None;
+    let mut after_tick = |_, splitter: &SplitterState, _: &MemoryValues| {
+        current_il = splitter.current_il();
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+    // without game time, entering the level starts it
+    let (mut real_time_events, _) = run_with(
+        SplitterState::new(false),
+        &settings,
+        &frames,
+        RunHooks::default(),
+    );
+    real_time_events.retain(|(_, action)| *action != TimerAction::ResumeGameTime);
+
+    assert_eq!(
+        events,
+        [
+            (2, TimerAction::Start),
+            (5, TimerAction::Reset),
+            (6, TimerAction::Start),
+        ]
+    );
+    assert_eq!(
+        real_time_events,
+        [
+            (1, TimerAction::Start),
+            (5, TimerAction::Reset),
+            (5, TimerAction::Start),
+        ]
+    );
+    assert_eq!(current_il, Some(Level::F1AncientCheese));
+}
+
+#[test]
+fn il_level_stays_while_the_timer_runs() {
+    let mut settings = Settings::for_mode(TimerMode::IL);
+    settings.reset_new_level = false;
+    let frames = [
+        frame("tower_1", 5.0, 0.0),
+        frame("medieval_1", 6.0, 0.0),
+        frame("medieval_1", 6.08, 0.08),
+        frame("medieval_2", 10.0, 4.0),
+        frame("tower_1", 15.0, 0.0),
+        frame("ruin_1", 16.0, 0.0),
+        frame("ruin_1", 16.08, 0.08),
+    ];
+    let mut current_il = Vec::new();
+    let mut after_tick = |_, splitter: &SplitterState, _: &MemoryValues| {
+        current_il.push(splitter.current_il());
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(events.first(), Some(&(2, TimerAction::Start)));
+    assert!(!events.contains(&(6, TimerAction::Start)));
+    assert_eq!(current_il[2], Some(Level::F1Pizzascape));
+    assert_eq!(current_il[6], Some(Level::F1Pizzascape));
+}
+
+#[test]
+fn new_game_plus_time_starts_at_the_entrance() {
+    let settings = Settings::for_mode(TimerMode::NewGamePlus);
+    let frames = [
+        frame("hub_loadingscreen", 3000.0, 0.0),
+        frame("tower_entrancehall", 3000.5, 0.5),
+        frame("tower_1", 3010.0, 0.0),
+    ];
+
+    let (events, game_time) = run(&settings, &frames);
+
+    assert_eq!(events, [(1, TimerAction::Start)]);
+    assert_eq!(game_time, 9.5);
+}
+
+#[test]
+fn individual_world_starts_when_leaving_a_level() {
+    let settings = Settings::for_mode(TimerMode::IW);
+    let frames = [
+        frame("tower_1", 100.0, 0.0),
+        frame("entrance_1", 101.0, 0.0),
+        frame("entrance_2", 110.0, 9.0),
+        frame("entrance_1", 120.0, 19.0),
+        frame("tower_1", 121.0, 0.0),
+        frame("tower_2", 130.0, 0.0),
+    ];
+
+    let (events, game_time) = run(&settings, &frames);
+
+    assert_eq!(events, [(4, TimerAction::Start)]);
+    assert_eq!(game_time, 9.0);
+}
+
+#[test]
+fn trace_keeps_room_changes_and_actions() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let mut game = FakeGame::new();
+    let addresses = fake_addresses();
+    let mut values = MemoryValues::default();
+    let mut splitter = SplitterState::new(true);
+    let mut trace = TraceRecorder::new();
+
+    let mut frames = vec![frame("Finalintro", 0.0, 0.0)];
+    // a few seconds of nothing happening only leaves keyframes behind
+    frames.extend((1..=360).map(|tick| frame("tower_entrancehall", tick as f64 / 120.0, 0.5)));
+
+    for frame in &frames {
+        game.write_frame(1, frame);
+        let ranges = game.ranges();
+        let process = FakeProcess { ranges: &ranges };
+        refresh_mem_values(&process, &addresses, &mut values).unwrap();
+        let actions = splitter.tick(&values, &settings, TimerState::NotRunning);
+        trace.record(&values, TimerState::NotRunning, &actions);
+    }
+
+    let mut lines = Vec::new();
+    trace.write_lines(
+        &settings,
+        splitter.custom_splits(),
+        splitter.route(),
+        true,
+        |line| lines.push(std::string::String::from(line)),
+    );
+
+    assert_eq!(lines[0], TRACE_HEADER);
+    assert!(lines[1].starts_with("settings mode=full_game campaign=detect start_enable=1"));
+    // the options are too long for one line and continue on a second one
+    assert!(lines[2].starts_with("settings "));
+    assert!(lines[2].ends_with("game_time_remove_loads=1"));
+    assert!(lines[3].starts_with("route entrance_1,medieval_1,ruin_1,dungeon_1,boss_pepperman,"));
+    let lines = &lines[3..];
+    assert_eq!(lines[1], "game_time 1");
+    assert_eq!(lines[2], "room 0 Finalintro");
+    assert_eq!(lines[3], "room 1 tower_entrancehall");
+    assert_eq!(lines[4], "frame 1 1 0 0.0 0.0 0 0 N R");
+    assert_eq!(lines[5], "frame 2 1 1 0.008333333333333333 0.5 0 0 N S");
+    assert_eq!(lines[6], "frame 122 1 1 1.0083333333333333 0.5 0 0 N -");
+    assert_eq!(lines[7], "frame 242 1 1 2.0083333333333333 0.5 0 0 N -");
+    assert_eq!(lines.len(), 8);
+}
+
+#[test]
+fn replaying_a_trace_gives_the_same_decisions() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_custom = true;
+    let mut game = FakeGame::new();
+    let addresses = fake_addresses();
+    let mut values = MemoryValues::default();
+    let mut splitter = SplitterState::new(true);
+    let mut custom_splits = CustomSplits::default();
+    custom_splits.push(CustomSplit::parse("entrance_* -> entrance_10").unwrap());
+    splitter.set_custom_splits(custom_splits);
+    let mut timer = TimerSim::new();
+    let mut trace = TraceRecorder::new();
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("entrance_1", 10.0, 0.0),
+        frame("entrance_10", 50.0, 40.0),
+        frame("entrance_1", 70.0, 60.0),
+        frame("tower_1", 71.0, 0.0),
+    ];
+
+    for frame in &frames {
+        game.write_frame(1, frame);
+        let ranges = game.ranges();
+        let process = FakeProcess { ranges: &ranges };
+        refresh_mem_values(&process, &addresses, &mut values).unwrap();
+        let timer_state = timer.state;
+        let actions = splitter.tick(&values, &settings, timer_state);
+        trace.record(&values, timer_state, &actions);
+        for action in actions {
+            timer.apply(action);
+        }
+    }
+
+    let mut log = std::string::String::from("some other log line\n");
+    trace.write_lines(
+        &settings,
+        splitter.custom_splits(),
+        splitter.route(),
+        true,
+        |line| {
+            log.push_str(line);
+            log.push('\n');
+        },
+    );
+    let output = replay(&log).unwrap();
+
+    assert_eq!(output[0], "replaying 6 frames, mode full_game");
+    assert_eq!(
+        output[1],
+        "tick 1: reset (ignored by timer) at 0:00.000 [idk] ( -> Finalintro)"
+    );
+    assert_eq!(
+        output[2],
+        "tick 2: start at 0:00.100 [Hub] (Finalintro -> tower_entrancehall)"
+    );
+    assert_eq!(
+        output[3],
+        "tick 4: split at 0:50.000 [John Gutter] (entrance_1 -> entrance_10)"
+    );
+    assert_eq!(
+        output[4],
+        "tick 6: split at 1:11.000 [Hub] (entrance_1 -> tower_1)"
+    );
+    assert_eq!(output.len(), 5);
+}
+
+#[test]
+fn escape_split_once_per_level_visit() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_escape = true;
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("forest_1", 10.0, 0.0),
+        frame("forest_john", 40.0, 30.0),
+        frame("forest_3", 45.0, 35.0),
+        frame("forest_john", 50.0, 40.0),
+        frame("forest_1", 70.0, 60.0),
+        frame("tower_3", 71.0, 0.0),
+    ];
+
+    let (events, _) = run(&settings, &frames);
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (3, TimerAction::Split),
+            (7, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn checkpoint_splits_on_secrets_and_escape() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_escape = true;
+    settings.splits_checkpoints = true;
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("medieval_1", 10.0, 0.0),
+        frame("medieval_2", 15.0, 5.0),
+        frame("medieval_secret2", 20.0, 10.0),
+        frame("medieval_2", 25.0, 15.0),
+        frame("medieval_secret2", 26.0, 16.0),
+        frame("medieval_10", 40.0, 30.0),
+        frame("medieval_1", 70.0, 60.0),
+        frame("tower_1", 71.0, 0.0),
+    ];
+
+    let (events, _) = run(&settings, &frames);
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (4, TimerAction::Split),
+            (7, TimerAction::Split),
+            (9, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn secret_splits_once_per_run() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_level_end = false;
+    settings.splits_secrets = true;
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("medieval_1", 10.0, 0.0),
+        frame("medieval_secret1", 20.0, 10.0),
+        frame("medieval_2", 25.0, 15.0),
+        frame("medieval_secret3", 26.0, 16.0),
+        frame("tower_1", 71.0, 0.0),
+        frame("medieval_1", 80.0, 0.0),
+        frame("medieval_secret1", 90.0, 10.0),
+        frame("medieval_2", 95.0, 15.0),
+    ];
+    let mut counts = Vec::new();
+    let mut after_tick = |_, splitter: &SplitterState, _: &MemoryValues| {
+        counts.push(splitter.secret_counts());
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(counts[3], (Some(1), 1));
+    assert_eq!(counts[6], (None, 2));
+    assert_eq!(counts[9], (Some(2), 2));
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (3, TimerAction::Split),
+            (5, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn custom_splits_match_room_prefixes() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_custom = true;
+    let mut custom_splits = CustomSplits::default();
+    custom_splits.push(CustomSplit::parse("medieval_* -> tower_1").unwrap());
+    custom_splits.push(CustomSplit::parse(" ruin_3->ruin_4 ").unwrap());
+    let mut splitter = SplitterState::new(true);
+    splitter.set_custom_splits(custom_splits);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("ruin_3", 10.0, 0.0),
+        frame("ruin_4", 15.0, 5.0),
+        frame("ruin_5", 20.0, 10.0),
+        frame("medieval_7", 30.0, 20.0),
+        frame("tower_2", 31.0, 0.0),
+        frame("medieval_1", 40.0, 0.0),
+        frame("tower_1", 41.0, 0.0),
+    ];
+
+    let (events, _) = run_with(splitter, &settings, &frames, RunHooks::default());
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (3, TimerAction::Split),
+            (8, TimerAction::Split)
+        ]
+    );
+    assert!(CustomSplit::parse("medieval_1 tower_1").is_err());
+    assert!(CustomSplit::parse("-> tower_1").is_err());
+}
+
+/**
+ * Finishes John Gutter twice, then Ancient Cheese without Pizzascape
+ */
+fn route_frames() -> [Frame; 14] {
+    [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("entrance_1", 10.0, 0.0),
+        frame("entrance_10", 50.0, 40.0),
+        frame("entrance_1", 70.0, 60.0),
+        frame("tower_1", 71.0, 0.0),
+        frame("entrance_1", 80.0, 0.0),
+        frame("entrance_10", 120.0, 40.0),
+        frame("entrance_1", 140.0, 60.0),
+        frame("tower_1", 141.0, 0.0),
+        frame("ruin_1", 150.0, 0.0),
+        frame("ruin_11", 190.0, 40.0),
+        frame("ruin_1", 210.0, 60.0),
+        frame("tower_1", 211.0, 0.0),
+    ]
+}
+
+#[test]
+fn route_only_splits_on_the_next_level() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_route = true;
+
+    let (events, _) = run(&settings, &route_frames());
+
+    assert_eq!(events, [(1, TimerAction::Start), (5, TimerAction::Split)]);
+}
+
+#[test]
+fn route_skips_a_missing_level_when_allowed() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_route = true;
+    settings.splits_route_skips = true;
+
+    let (events, _) = run(&settings, &route_frames());
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (5, TimerAction::Split),
+            (13, TimerAction::SkipSplit),
+            (13, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn route_follows_the_livesplit_split_index() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_route = true;
+    // the runner skips pizzascape by hand after finishing john gutter
+    let split_index = |index| (index >= 6).then_some(2);
+
+    let hooks = RunHooks {
+        split_index: Some(&split_index),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &route_frames(), hooks);
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (5, TimerAction::Split),
+            (13, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn route_keeps_its_position_past_the_other_splits() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_route = true;
+    settings.splits_escape = true;
+
+    let (events, _) = run(&settings, &route_frames());
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (3, TimerAction::Split),
+            (5, TimerAction::Split),
+            (7, TimerAction::Split),
+            (11, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn route_can_be_picked_by_the_runner() {
+    let mut settings = Settings::for_mode(TimerMode::FullGame);
+    settings.splits_route = true;
+    let mut splitter = SplitterState::new(true);
+    splitter.set_route(Route::parse("entrance_1, ruin_1").unwrap());
+
+    let (events, _) = run_with(splitter, &settings, &route_frames(), RunHooks::default());
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (5, TimerAction::Split),
+            (13, TimerAction::Split)
+        ]
+    );
+    assert!(Route::parse("entrance_1, tower_1").is_err());
+    assert!(Route::parse("entrance_1,,ruin_1").is_err());
+}
+
+#[test]
+fn undoing_a_split_lets_it_happen_again() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("tower_5", 10.0, 0.0),
+        frame("tower_finalhallway", 11.0, 0.0),
+        frame("tower_5", 12.0, 0.0),
+        frame("tower_finalhallway", 13.0, 0.0),
+    ];
+    // the runner undoes the out of bounds split after going back to tower_5
+    let split_index = |index| (index == 4).then_some(0);
+
+    let hooks = RunHooks {
+        split_index: Some(&split_index),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (3, TimerAction::Split),
+            (5, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn skipping_a_split_counts_the_level_as_done() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("entrance_1", 10.0, 0.0),
+        frame("entrance_10", 50.0, 40.0),
+        frame("entrance_1", 70.0, 60.0),
+        frame("tower_1", 71.0, 0.0),
+    ];
+    // the runner skips john gutter's split by hand in the escape
+    let split_index = |index| (index >= 4).then_some(1);
+
+    let hooks = RunHooks {
+        split_index: Some(&split_index),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(events, [(1, TimerAction::Start)]);
+}
+
+#[test]
+fn resetting_by_hand_forgets_the_last_run() {
+    let settings = Settings::for_mode(TimerMode::FullGame);
+    let frames = [
+        frame("Finalintro", 0.0, 0.0),
+        frame("tower_entrancehall", 0.1, 0.1),
+        frame("entrance_1", 10.0, 0.0),
+        frame("entrance_10", 50.0, 40.0),
+        frame("entrance_1", 70.0, 60.0),
+        frame("tower_1", 71.0, 0.0),
+        frame("tower_1", 72.0, 0.0),
+        frame("tower_2", 73.0, 0.0),
+        frame("entrance_1", 80.0, 0.0),
+        frame("tower_1", 82.0, 0.0),
+    ];
+    // the runner resets and starts again by hand, livesplit's split index starts over
+    let timer_state = |index| match index {
+        6 => Some(TimerState::NotRunning),
+        7 => Some(TimerState::Running),
+        _ => None,
+    };
+    let split_index = |index| (index >= 7).then_some(0);
+    let hooks = RunHooks {
+        split_index: Some(&split_index),
+        timer_state: Some(&timer_state),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    // leaving john gutter without reaching its unlock room in the new run doesn't split
+    assert_eq!(events, [(1, TimerAction::Start), (5, TimerAction::Split)]);
+}
+
+#[test]
+fn loads_pause_game_time_without_the_buffer_helper() {
+    let settings = Settings::for_mode(TimerMode::NewGamePlus);
+    let frames = [
+        frame("hub_loadingscreen", 0.0, 0.0),
+        frame("tower_entrancehall", 0.0, 0.0),
+        frame("tower_1", 0.0, 0.0),
+        frame("hub_loadingscreen", 0.0, 0.0),
+        frame("hub_loadingscreen", 0.0, 0.0),
+        frame("tower_entrancehall", 0.0, 0.0),
+        frame("tower_1", 0.0, 0.0),
+    ];
+
+    let (events, _) = run_with(
+        SplitterState::new(false),
+        &settings,
+        &frames,
+        RunHooks::default(),
+    );
+
+    assert_eq!(
+        events,
+        [
+            (1, TimerAction::Start),
+            (2, TimerAction::ResumeGameTime),
+            (3, TimerAction::PauseGameTime),
+            (3, TimerAction::Reset),
+            (5, TimerAction::Start),
+            (6, TimerAction::ResumeGameTime)
+        ]
+    );
+}
+
+#[test]
+fn lap_portal_starts_a_new_lap() {
+    let mut settings = Settings::for_mode(TimerMode::IL);
+    settings.splits_rooms = false;
+    settings.splits_laps = true;
+    let frames = [
+        frame("entrance_1", 0.0, 0.08),
+        frame("entrance_10", 40.0, 40.0),
+        frame("entrance_9", 45.0, 45.0),
+        frame("entrance_10", 46.0, 46.0),
+        frame("entrance_1", 60.0, 60.0),
+        frame("entrance_10", 61.0, 61.0),
+        frame("entrance_1", 80.0, 80.0),
+        frame("entrance_10", 81.0, 81.0),
+    ];
+    let mut laps = Vec::new();
+    let mut after_tick = |_, splitter: &SplitterState, _: &MemoryValues| {
+        laps.push(splitter.current_lap());
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+
+    let (events, _) = run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(laps, [1, 1, 1, 1, 1, 2, 2, 3]);
+    assert_eq!(
+        events,
+        [
+            (0, TimerAction::Start),
+            (5, TimerAction::Split),
+            (7, TimerAction::Split)
+        ]
+    );
+}
+
+#[test]
+fn every_known_room_maps_to_one_level() {
+    // sorted without duplicates, so a name is in a table at most once and the binary search works
+    for table in level_room_tables() {
+        assert!(table.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    let levels = full_game_route()
+        .iter()
+        .chain(collectible_levels())
+        .chain(&[Level::F1Tutorial, Level::F1TutorialNoise]);
+    for level in levels {
+        let rooms = checkpoint_rooms(level)
+            .iter()
+            .copied()
+            .chain([get_starting_room(level)]);
+        for room in rooms {
+            assert_eq!(get_current_level(room, Level::Unknown), *level, "{room}");
+        }
+    }
+
+    // the longest prefix wins, and prefixes only match the start of a name
+    assert_eq!(
+        get_current_level("tower_tutorial2N", Level::Hub),
+        Level::F1TutorialNoise
+    );
+    assert_eq!(
+        get_current_level("tower_tutorial2", Level::Hub),
+        Level::F1Tutorial
+    );
+    assert_eq!(get_current_level("tower_5", Level::Hub), Level::Hub);
+    assert_eq!(
+        get_current_level("boss_pizzafacehub", Level::PizzaFace),
+        Level::Hub
+    );
+    assert_eq!(
+        get_current_level("kidsparty_war_1", Level::Hub),
+        Level::F5DMAS
+    );
+    assert_eq!(
+        get_current_level("boss_pizzafacefinale", Level::Hub),
+        Level::Unknown
+    );
+    assert_eq!(
+        get_current_level("tower_5", Level::F5CrumblingTower),
+        Level::F5CrumblingTower
+    );
+}
+
+#[test]
+fn split_rule_rooms_belong_to_their_level() {
+    // every room the split rules look at is placed in its level by the level tables
+    let levels = full_game_route()
+        .iter()
+        .chain(collectible_levels())
+        .chain(&[Level::F1Tutorial, Level::F1TutorialNoise]);
+    for level in levels {
+        for room in checkpoint_rooms(level) {
+            assert!(is_known_room(room), "{room}");
+            assert_eq!(get_current_level(room, Level::Unknown), *level, "{room}");
+        }
+        let starting_room = get_starting_room(level);
+        assert!(is_known_room(starting_room), "{level:?}");
+        assert_eq!(get_current_level(starting_room, Level::Unknown), *level);
+    }
+
+    // rooms the split rules don't use are known by their level
+    let mut unknown_rooms = UnknownRooms::new();
+    assert!(!unknown_rooms.check(&room_cstring("entrance_3")));
+    assert!(!unknown_rooms.check(&room_cstring("forest_2")));
+    assert!(!unknown_rooms.check(&room_cstring("secret_entrance")));
+    assert!(!unknown_rooms.check(&room_cstring("")));
+    assert!(unknown_rooms.check(&room_cstring("newlevel_1")));
+    assert!(!unknown_rooms.check(&room_cstring("newlevel_1")));
+    assert!(unknown_rooms.check(&room_cstring("newmenu")));
+}
+
+/**
+ * Best times kept in a list instead of the settings map
+ */
+#[derive(Default)]
+struct ListBestTimes(Vec<(std::string::String, f64)>);
+
+impl BestTimes for ListBestTimes {
+    fn get(&mut self, key: &str) -> Option<f64> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, seconds)| *seconds)
+    }
+
+    fn set(&mut self, key: &str, seconds: f64) {
+        self.0.retain(|(name, _)| name != key);
+        self.0.push((key.into(), seconds));
+    }
+}
+
+#[test]
+fn practice_keeps_the_best_room_and_level_times() {
+    let settings = Settings::for_mode(TimerMode::IL);
+    let attempt = |room_2_secs: f64| {
+        [
+            frame("tower_1", 0.0, 0.0),
+            frame("entrance_1", 0.0, 0.0),
+            frame("entrance_2", 0.0, 10.0),
+            frame("entrance_3", 0.0, 10.0 + room_2_secs),
+            frame("rank_room", 0.0, 30.0 + room_2_secs),
+            frame("tower_1", 0.0, 0.0),
+        ]
+    };
+    let frames: Vec<Frame> = [attempt(8.0), attempt(5.0), attempt(6.0)].concat();
+    let mut best_times = ListBestTimes::default();
+    let mut practice = Practice::new();
+    let mut seen = Vec::new();
+    let mut after_tick = |_, splitter: &SplitterState, values: &MemoryValues| {
+        practice.tick(values, splitter.current_level(), &mut best_times);
+        seen.push((practice.best_for_room(), practice.delta_vs_best()));
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+
+    run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(best_times.get("practice_room_entrance_2"), Some(5.0));
+    assert_eq!(best_times.get("practice_room_entrance_1"), Some(10.0));
+    assert_eq!(best_times.get("practice_level_entrance_1"), Some(35.0));
+    // entering entrance_2 on the third attempt shows the best, leaving it the delta
+    assert_eq!(seen[14], (Some(5.0), Some(0.0)));
+    assert_eq!(seen[15].1, Some(1.0));
+    assert_eq!(practice.best_for_level(), Some(35.0));
+    assert_eq!(format_time(-1.0, true).as_str(), "-1.00");
+    assert_eq!(format_time(65.056, false).as_str(), "1:05.06");
+}
+
+#[test]
+fn practice_keeps_the_attempt_when_the_escape_ends_in_the_first_room() {
+    let settings = Settings::for_mode(TimerMode::IL);
+    // the escape of entrance goes back out through entrance_1, a restart puts the level timer back
+    let attempt = |room_2_secs: f64| {
+        [
+            frame("tower_1", 0.0, 0.0),
+            frame("entrance_1", 0.0, 0.0),
+            frame("entrance_2", 0.0, 10.0),
+            frame("entrance_10", 0.0, 10.0 + room_2_secs),
+            frame("entrance_2", 0.0, 20.0 + room_2_secs),
+            frame("entrance_1", 0.0, 25.0 + room_2_secs),
+            frame("rank_room", 0.0, 30.0 + room_2_secs),
+            frame("tower_1", 0.0, 0.0),
+        ]
+    };
+    let restart = [
+        frame("entrance_1", 0.0, 0.0),
+        frame("entrance_2", 0.0, 10.0),
+        frame("entrance_1", 0.0, 0.0),
+        frame("entrance_2", 0.0, 9.0),
+        frame("entrance_10", 0.0, 15.0),
+        frame("tower_1", 0.0, 0.0),
+    ];
+    let frames: Vec<Frame> = [&attempt(8.0)[..], &attempt(5.0), &restart].concat();
+    let mut best_times = ListBestTimes::default();
+    let mut practice = Practice::new();
+    let mut seen = Vec::new();
+    let mut after_tick = |_, splitter: &SplitterState, values: &MemoryValues| {
+        practice.tick(values, splitter.current_level(), &mut best_times);
+        seen.push(practice.delta_at_room_change());
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+
+    run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    assert_eq!(best_times.get("practice_level_entrance_1"), Some(35.0));
+    assert_eq!(
+        best_times.get("reference_entrance_1_entrance_10_0"),
+        Some(15.0)
+    );
+    assert_eq!(
+        best_times.get("reference_entrance_1_entrance_1_0"),
+        Some(30.0)
+    );
+    // the second attempt is compared with the first all the way to the end
+    assert_eq!(seen[13], Some(-3.0));
+    // after the restart entrance_2 is the first visit again
+    assert_eq!(seen[19], Some(-1.0));
+    assert_eq!(seen[20], Some(0.0));
+}
+
+#[test]
+fn practice_compares_rooms_with_the_best_run_of_the_level() {
+    let settings = Settings::for_mode(TimerMode::IL);
+    // the escape goes back through entrance_2
+    let attempt = |room_2_secs: f64| {
+        [
+            frame("tower_1", 0.0, 0.0),
+            frame("entrance_1", 0.0, 0.0),
+            frame("entrance_2", 0.0, 10.0),
+            frame("entrance_3", 0.0, 10.0 + room_2_secs),
+            frame("entrance_2", 0.0, 20.0 + room_2_secs),
+            frame("rank_room", 0.0, 30.0 + room_2_secs),
+            frame("tower_1", 0.0, 0.0),
+        ]
+    };
+    let frames: Vec<Frame> = [attempt(8.0), attempt(5.0), attempt(6.0)].concat();
+    let mut best_times = ListBestTimes::default();
+    let mut practice = Practice::new();
+    let mut seen = Vec::new();
+    let mut after_tick = |_, splitter: &SplitterState, values: &MemoryValues| {
+        practice.tick(values, splitter.current_level(), &mut best_times);
+        seen.push((
+            practice.delta_at_room_change(),
+            practice.predicted_level_time(),
+        ));
+    };
+    let hooks = RunHooks {
+        after_tick: Some(&mut after_tick),
+        ..Default::default()
+    };
+
+    run_with(SplitterState::new(true), &settings, &frames, hooks);
+
+    // nothing to compare with on the first attempt
+    assert!(seen[..7].iter().all(|&compared| compared == (None, None)));
+    assert_eq!(seen[9], (Some(0.0), Some(38.0)));
+    assert_eq!(seen[10], (Some(-3.0), Some(35.0)));
+    assert_eq!(seen[11], (Some(-3.0), Some(35.0)));
+    // the second attempt became the reference
+    assert_eq!(seen[16], (Some(0.0), Some(35.0)));
+    assert_eq!(seen[17], (Some(1.0), Some(36.0)));
+    assert_eq!(seen[18], (Some(1.0), Some(36.0)));
+    assert_eq!(
+        best_times.get("reference_entrance_1_entrance_2_1"),
+        Some(25.0)
+    );
+}
@@ -0,0 +1,286 @@
+use asr::arrayvec::{ArrayString, ArrayVec};
+use asr::string::ArrayCString;
+use asr::timer::TimerState;
+use asr::{itoa, ryu};
+
+use crate::custom_splits::CustomSplits;
+use crate::memory::ROOM_NAME_SIZE_CAP;
+use crate::room_names;
+use crate::route::{Route, ROUTE_TEXT_CAP};
+use crate::settings::Settings;
+use crate::splitter::TimerAction;
+use crate::MemoryValues;
+
+pub const TRACE_HEADER: &str = "pizza tower trace v1";
+
+// how many frames are kept, older ones get overwritten
+const TRACE_CAPACITY: usize = 4096;
+// distinct room names that can be remembered, pizza tower has a few hundred
+const ROOM_CAPACITY: usize = 512;
+// a frame is recorded at least this often even if nothing happens, so the timers can be followed
+const KEYFRAME_TICKS: u32 = 120;
+// the room index used once the room table is full
+const UNKNOWN_ROOM: u16 = u16::MAX;
+// options that don't fit continue on another settings line
+const SETTINGS_LINE_CAP: usize = 256;
+
+/**
+ * The code used for a timer action in the trace, game time and load removal aren't recorded as actions
+ */
+pub fn action_code(action: &TimerAction) -> Option<char> {
+    match action {
+        TimerAction::Start => Some('S'),
+        TimerAction::Split => Some('P'),
+        TimerAction::SkipSplit => Some('K'),
+        TimerAction::Reset => Some('R'),
+        TimerAction::SetGameTime(_) | TimerAction::PauseGameTime | TimerAction::ResumeGameTime => {
+            None
+        }
+    }
+}
+
+pub fn timer_state_code(timer_state: TimerState) -> char {
+    match timer_state {
+        TimerState::NotRunning => 'N',
+        TimerState::Running => 'R',
+        TimerState::Paused => 'P',
+        TimerState::Ended => 'E',
+        _ => '?',
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct TraceFrame {
+    tick: u32,
+    room_id: i32,
+    room: u16,
+    end_of_level: u8,
+    boss_hp: u8,
+    timer_state: u8,
+    actions: [u8; 4],
+    file_secs: f64,
+    level_secs: f64,
+}
+
+/**
+ * Records what the game reported and what the splitter decided, for bug reports.
+ *
+ * Only ticks where something other than the timers changed are kept, plus one every KEYFRAME_TICKS,
+ * so the buffer covers a lot more than TRACE_CAPACITY ticks of a run.
+ */
+pub struct TraceRecorder {
+    frames: [TraceFrame; TRACE_CAPACITY],
+    next_frame: usize,
+    frame_count: usize,
+    rooms: ArrayVec<ArrayCString<ROOM_NAME_SIZE_CAP>, ROOM_CAPACITY>,
+    tick: u32,
+    last_recorded_tick: Option<u32>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self {
+            frames: [TraceFrame::default(); TRACE_CAPACITY],
+            next_frame: 0,
+            frame_count: 0,
+            rooms: ArrayVec::new(),
+            tick: 0,
+            last_recorded_tick: None,
+        }
+    }
+
+    fn room_index(&mut self, room_name: &ArrayCString<ROOM_NAME_SIZE_CAP>) -> u16 {
+        if let Some(index) = self.rooms.iter().position(|room| room == room_name) {
+            return index as u16;
+        }
+        match self.rooms.try_push(*room_name) {
+            Ok(()) => (self.rooms.len() - 1) as u16,
+            Err(_) => UNKNOWN_ROOM,
+        }
+    }
+
+    /**
+     * Call once per tick after the splitter ran, with the timer state the splitter was given
+     */
+    pub fn record(
+        &mut self,
+        mem_values: &MemoryValues,
+        timer_state: TimerState,
+        actions: &[TimerAction],
+    ) {
+        self.tick = self.tick.wrapping_add(1);
+
+        let file_secs = mem_values.file_minutes.current * 60.0 + mem_values.file_seconds.current;
+        let level_secs = mem_values.level_minutes.current * 60.0 + mem_values.level_seconds.current;
+        let level_secs_old = mem_values.level_minutes.old * 60.0 + mem_values.level_seconds.old;
+
+        let mut codes = [0; 4];
+        let mut code_count = 0;
+        for code in actions.iter().filter_map(action_code) {
+            if code_count < codes.len() {
+                codes[code_count] = code as u8;
+                code_count += 1;
+            }
+        }
+
+        let keyframe_due = match self.last_recorded_tick {
+            Some(last) => self.tick.wrapping_sub(last) >= KEYFRAME_TICKS,
+            None => true,
+        };
+
+        // the level timer decreasing and the first tenth of a second of a level matter for resets and il starts
+        let interesting = keyframe_due
+            || code_count > 0
+            || mem_values.room_name.changed()
+            || mem_values.room_id.changed()
+            || mem_values.end_of_level.changed()
+            || mem_values.boss_hp.changed()
+            || level_secs < level_secs_old
+            || (level_secs > 0.0 && level_secs <= 0.1);
+
+        if !interesting {
+            return;
+        }
+
+        let room = self.room_index(&mem_values.room_name.current);
+        self.frames[self.next_frame] = TraceFrame {
+            tick: self.tick,
+            room_id: mem_values.room_id.current,
+            room,
+            end_of_level: mem_values.end_of_level.current,
+            boss_hp: mem_values.boss_hp.current,
+            timer_state: timer_state_code(timer_state) as u8,
+            actions: codes,
+            file_secs,
+            level_secs,
+        };
+        self.next_frame = (self.next_frame + 1) % TRACE_CAPACITY;
+        self.frame_count = (self.frame_count + 1).min(TRACE_CAPACITY);
+        self.last_recorded_tick = Some(self.tick);
+    }
+
+    /**
+     * Write the trace to the log, one line per message
+     */
+    pub fn dump(
+        &self,
+        settings: &Settings,
+        custom_splits: &CustomSplits,
+        route: &Route,
+        game_time_available: bool,
+    ) {
+        self.write_lines(
+            settings,
+            custom_splits,
+            route,
+            game_time_available,
+            asr::print_message,
+        );
+    }
+
+    /**
+     * Hand every line of the trace to the writer:
+     *
+     * header, settings lines with the options, "custom_split <from> -> <to>" for every custom split, "route <room>,<room>,..." with the first room of every level of the route, a game_time line,
+     * then "room <index> <name>" for every room
+     * and "frame <tick> <room id> <room index> <file secs> <level secs> <end fade> <boss hp> <timer state> <actions>" from oldest to newest
+     */
+    pub fn write_lines(
+        &self,
+        settings: &Settings,
+        custom_splits: &CustomSplits,
+        route: &Route,
+        game_time_available: bool,
+        mut write_line: impl FnMut(&str),
+    ) {
+        write_line(TRACE_HEADER);
+
+        let mut line = ArrayString::<SETTINGS_LINE_CAP>::new();
+        line.push_str("settings mode=");
+        line.push_str(settings.timer_mode.current.name());
+        line.push_str(" campaign=");
+        line.push_str(settings.campaign.name());
+        for (key, value) in settings.flags() {
+            let mut field = ArrayString::<64>::new();
+            field.push(' ');
+            field.push_str(key);
+            field.push_str(if value { "=1" } else { "=0" });
+            // a full line is written and the options continue on another settings line
+            if line.try_push_str(&field).is_err() {
+                write_line(&line);
+                line.clear();
+                line.push_str("settings");
+                line.push_str(&field);
+            }
+        }
+        write_line(&line);
+
+        for split in custom_splits.iter() {
+            let mut line = ArrayString::<{ ROOM_NAME_SIZE_CAP * 2 + 20 }>::new();
+            line.push_str("custom_split ");
+            line.push_str(split.old_room_pattern());
+            line.push_str(" -> ");
+            line.push_str(split.new_room_pattern());
+            write_line(&line);
+        }
+
+        let mut line = ArrayString::<{ ROUTE_TEXT_CAP + 8 }>::new();
+        line.push_str("route ");
+        for (index, level) in route.iter().enumerate() {
+            if index > 0 {
+                line.push(',');
+            }
+            let _ = line.try_push_str(room_names::get_starting_room(level));
+        }
+        write_line(&line);
+
+        write_line(if game_time_available {
+            "game_time 1"
+        } else {
+            "game_time 0"
+        });
+
+        let mut int_buffer = itoa::Buffer::new();
+        for (index, room) in self.rooms.iter().enumerate() {
+            let mut line = ArrayString::<{ ROOM_NAME_SIZE_CAP + 16 }>::new();
+            line.push_str("room ");
+            line.push_str(int_buffer.format(index));
+            line.push(' ');
+            line.push_str(room.validate_utf8().unwrap_or("(invalid utf-8 string)"));
+            write_line(&line);
+        }
+
+        let first = (self.next_frame + TRACE_CAPACITY - self.frame_count) % TRACE_CAPACITY;
+        for offset in 0..self.frame_count {
+            let frame = &self.frames[(first + offset) % TRACE_CAPACITY];
+            let mut float_buffer = ryu::Buffer::new();
+            let mut line = ArrayString::<128>::new();
+
+            line.push_str("frame ");
+            line.push_str(int_buffer.format(frame.tick));
+            line.push(' ');
+            line.push_str(int_buffer.format(frame.room_id));
+            line.push(' ');
+            line.push_str(int_buffer.format(frame.room));
+            line.push(' ');
+            line.push_str(float_buffer.format(frame.file_secs));
+            line.push(' ');
+            line.push_str(float_buffer.format(frame.level_secs));
+            line.push(' ');
+            line.push_str(int_buffer.format(frame.end_of_level));
+            line.push(' ');
+            line.push_str(int_buffer.format(frame.boss_hp));
+            line.push(' ');
+            line.push(frame.timer_state as char);
+            line.push(' ');
+            if frame.actions[0] == 0 {
+                line.push('-');
+            }
+            for &code in frame.actions.iter().take_while(|&&code| code != 0) {
+                line.push(code as char);
+            }
+
+            write_line(&line);
+        }
+    }
+}